@@ -0,0 +1,359 @@
+#![allow(clippy::needless_return)]
+
+use ethnum::{U256};
+
+
+// Word arithmetic as the yellow paper defines it. Words are unsigned, the
+// signed instructions read them as two's complement. Division by zero gives
+// zero instead of trapping, and shifts of 256 bits or more shift everything out
+
+pub fn div(a: U256, b: U256) -> U256 {
+    if b == 0 {
+        return U256::new(0);
+    }
+    return a / b;
+}
+
+pub fn modulo(a: U256, b: U256) -> U256 {
+    if b == 0 {
+        return U256::new(0);
+    }
+    return a % b;
+}
+
+// rounds towards zero, the only overflow, -2^255 / -1, wraps back to -2^255
+pub fn sdiv(a: U256, b: U256) -> U256 {
+    if b == 0 {
+        return U256::new(0);
+    }
+    return a.as_i256().wrapping_div(b.as_i256()).as_u256();
+}
+
+// the result takes the sign of the dividend
+pub fn smod(a: U256, b: U256) -> U256 {
+    if b == 0 {
+        return U256::new(0);
+    }
+    return a.as_i256().wrapping_rem(b.as_i256()).as_u256();
+}
+
+// the sum and the product are taken modulo n before they are truncated to 256 bits
+pub fn addmod(a: U256, b: U256, n: U256) -> U256 {
+    if n == 0 {
+        return U256::new(0);
+    }
+    let (low, carry): (U256, bool) = a.overflowing_add(b);
+    return rem_512(U256::from(carry as u8), low, n);
+}
+
+pub fn mulmod(a: U256, b: U256, n: U256) -> U256 {
+    if n == 0 {
+        return U256::new(0);
+    }
+    let (high, low): (U256, U256) = mul_512(a, b);
+    return rem_512(high, low, n);
+}
+
+// full 512 bit product as (high, low) words, schoolbook over 128 bit halves
+fn mul_512(a: U256, b: U256) -> (U256, U256) {
+    let (a1, a0): (U256, U256) = (a >> 128, a & U256::from(u128::MAX));
+    let (b1, b0): (U256, U256) = (b >> 128, b & U256::from(u128::MAX));
+    let low_low: U256 = a0 * b0;
+    let middle_1: U256 = a0 * b1;
+    let middle_2: U256 = a1 * b0;
+    let high_high: U256 = a1 * b1;
+
+    let (low, carry_1): (U256, bool) = low_low.overflowing_add(middle_1 << 128);
+    let (low, carry_2): (U256, bool) = low.overflowing_add(middle_2 << 128);
+    let high: U256 = high_high + (middle_1 >> 128) + (middle_2 >> 128) + U256::from(carry_1 as u8) + U256::from(carry_2 as u8);
+    return (high, low);
+}
+
+// remainder of the 512 bit number high * 2^256 + low, bit by bit
+fn rem_512(high: U256, low: U256, n: U256) -> U256 {
+    let mut remainder: U256 = U256::new(0);
+    for word in [high, low] {
+        for bit in (0..256u32).rev() {
+            // the remainder stays below n, doubling it overflows at most one bit
+            let overflow: bool = remainder.leading_zeros() == 0;
+            remainder = (remainder << 1) | ((word >> bit) & U256::ONE);
+            if overflow || remainder >= n {
+                remainder = remainder.wrapping_sub(n);
+            }
+        }
+    }
+    return remainder;
+}
+
+// square and multiply over the whole exponent, modulo 2^256
+pub fn exp(base: U256, exponent: U256) -> U256 {
+    let mut result: U256 = U256::new(1);
+    let mut square: U256 = base;
+    let mut exponent: U256 = exponent;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(square);
+        }
+        square = square.wrapping_mul(square);
+        exponent >>= 1;
+    }
+    return result;
+}
+
+// extends the sign bit of byte `b`, counting from the least significant one
+pub fn signextend(b: U256, x: U256) -> U256 {
+    if b >= 31 {
+        return x;
+    }
+    let bit: u32 = b.as_u32() * 8 + 7;
+    let mask: U256 = (U256::new(1) << bit) - 1;
+    if (x >> bit) & 1 == 1 {
+        return x | !mask;
+    }
+    return x & mask;
+}
+
+// byte `i` of `x`, counting from the most significant one
+pub fn byte(i: U256, x: U256) -> U256 {
+    if i >= 32 {
+        return U256::new(0);
+    }
+    return (x >> (8 * (31 - i.as_u32()))) & 0xFF;
+}
+
+pub fn shl(shift: U256, value: U256) -> U256 {
+    if shift >= 256 {
+        return U256::new(0);
+    }
+    return value << shift.as_u32();
+}
+
+pub fn shr(shift: U256, value: U256) -> U256 {
+    if shift >= 256 {
+        return U256::new(0);
+    }
+    return value >> shift.as_u32();
+}
+
+// shifts in copies of the sign bit
+pub fn sar(shift: U256, value: U256) -> U256 {
+    let negative: bool = value.leading_zeros() == 0;
+    if shift >= 256 {
+        return if negative { U256::MAX } else { U256::new(0) };
+    }
+    return (value.as_i256() >> shift.as_u32()).as_u256();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: U256 = U256::MAX;
+    // -2^255, the most negative word
+    const MIN: U256 = U256::from_words(1 << 127, 0);
+
+    fn n(value: u128) -> U256 {
+        return U256::new(value);
+    }
+
+    // two's complement -value
+    fn neg(value: u128) -> U256 {
+        return U256::new(value).wrapping_neg();
+    }
+
+    fn check(name: &str, f: fn(U256, U256) -> U256, cases: &[(U256, U256, U256)]) {
+        for (i, (a, b, expected)) in cases.iter().enumerate() {
+            assert_eq!(f(*a, *b), *expected, "{} case {}: ({:#x}, {:#x})", name, i, a, b);
+        }
+    }
+
+    fn check_3(name: &str, f: fn(U256, U256, U256) -> U256, cases: &[(U256, U256, U256, U256)]) {
+        for (i, (a, b, c, expected)) in cases.iter().enumerate() {
+            assert_eq!(f(*a, *b, *c), *expected, "{} case {}: ({:#x}, {:#x}, {:#x})", name, i, a, b, c);
+        }
+    }
+
+    #[test]
+    fn test_div() {
+        check("DIV", div, &[
+            (n(10), n(3), n(3)),
+            (n(3), n(10), n(0)),
+            (MAX, n(1), MAX),
+            (MAX, MAX, n(1)),
+            (MAX, n(2), MIN - 1),
+            (n(1), n(0), n(0)),
+            (n(0), n(0), n(0)),
+            (MAX, n(0), n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_modulo() {
+        check("MOD", modulo, &[
+            (n(10), n(3), n(1)),
+            (n(3), n(10), n(3)),
+            (MAX, n(2), n(1)),
+            (MAX, MAX, n(0)),
+            (n(5), n(0), n(0)),
+            (MAX, n(0), n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_sdiv() {
+        check("SDIV", sdiv, &[
+            (n(10), n(3), n(3)),
+            (neg(10), n(3), neg(3)),
+            (n(10), neg(3), neg(3)),
+            (neg(10), neg(3), n(3)),
+            (neg(1), MIN, n(0)),
+            (MIN, n(1), MIN),
+            (MIN, neg(1), MIN),
+            (MIN, MIN, n(1)),
+            (n(0), neg(1), n(0)),
+            (neg(1), neg(1), n(1)),
+            (n(10), n(0), n(0)),
+            (MIN, n(0), n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_smod() {
+        check("SMOD", smod, &[
+            (n(10), n(3), n(1)),
+            (neg(10), n(3), neg(1)),
+            (n(10), neg(3), n(1)),
+            (neg(10), neg(3), neg(1)),
+            (neg(1), MIN, neg(1)),
+            (MIN, neg(1), n(0)),
+            (MIN, MIN, n(0)),
+            (MIN, n(3), neg(2)),
+            (n(10), n(0), n(0)),
+            (neg(10), n(0), n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_addmod() {
+        check_3("ADDMOD", addmod, &[
+            (n(10), n(10), n(8), n(4)),
+            (MAX, n(2), n(2), n(1)),
+            (MAX, MAX, MAX, n(0)),
+            (MAX, MAX, MAX - 1, n(2)),
+            (MAX, n(1), n(10), n(6)),
+            (MIN, MIN, MAX, n(1)),
+            (n(1), n(2), n(0), n(0)),
+            (MAX, MAX, n(0), n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_mulmod() {
+        check_3("MULMOD", mulmod, &[
+            (n(10), n(10), n(8), n(4)),
+            (MAX, MAX, MAX, n(0)),
+            (MAX, MAX, MAX - 1, n(1)),
+            (MAX, MAX, n(12), n(9)),
+            (MIN, n(2), MAX, n(1)),
+            (MIN, MIN, MAX, MIN >> 1),
+            (n(1) << 128, n(1) << 128, MAX, n(1)),
+            (n(2), n(3), n(0), n(0)),
+            (MAX, MAX, n(0), n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_exp() {
+        check("EXP", exp, &[
+            (n(2), n(10), n(1024)),
+            (n(2), n(255), MIN),
+            (n(2), n(256), n(0)),
+            (n(2), (n(1) << 32) + n(1), n(0)),
+            (n(3), n(0), n(1)),
+            (n(0), n(0), n(1)),
+            (n(0), n(5), n(0)),
+            (MAX, n(2), n(1)),
+            (MAX, n(3), MAX),
+            (MAX, MAX, MAX),
+            (n(1), MAX, n(1)),
+        ]);
+    }
+
+    #[test]
+    fn test_signextend() {
+        check("SIGNEXTEND", signextend, &[
+            (n(0), n(0x7F), n(0x7F)),
+            (n(0), n(0xFF), MAX),
+            (n(0), n(0x12F0), neg(0x10)),
+            (n(0), n(0x1270), n(0x70)),
+            (n(1), n(0x8000), neg(0x8000)),
+            (n(1), n(0xFF7FFF), n(0x7FFF)),
+            (n(30), n(1) << 247, MAX << 247),
+            (n(30), (n(1) << 247) - n(1), (n(1) << 247) - n(1)),
+            (n(31), MIN, MIN),
+            (n(31), n(0xFF), n(0xFF)),
+            (n(32), n(0xFF), n(0xFF)),
+            (MAX, n(0xFF), n(0xFF)),
+        ]);
+    }
+
+    #[test]
+    fn test_byte() {
+        check("BYTE", byte, &[
+            (n(0), MIN, n(0x80)),
+            (n(31), n(0xFF), n(0xFF)),
+            (n(30), n(0x1234), n(0x12)),
+            (n(1), MIN, n(0)),
+            (n(31), MIN, n(0)),
+            (n(32), MAX, n(0)),
+            (MAX, MAX, n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_shl() {
+        check("SHL", shl, &[
+            (n(0), n(1), n(1)),
+            (n(1), n(1), n(2)),
+            (n(255), n(1), MIN),
+            (n(256), n(1), n(0)),
+            (MAX, n(1), n(0)),
+            (n(0), MAX, MAX),
+            (n(1), MAX, MAX - 1),
+            (n(255), MAX, MIN),
+        ]);
+    }
+
+    #[test]
+    fn test_shr() {
+        check("SHR", shr, &[
+            (n(1), n(2), n(1)),
+            (n(1), n(1), n(0)),
+            (n(255), MIN, n(1)),
+            (n(256), MIN, n(0)),
+            (n(0), MAX, MAX),
+            (n(1), MAX, MIN - 1),
+            (MAX, MAX, n(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_sar() {
+        check("SAR", sar, &[
+            (n(1), n(2), n(1)),
+            (n(0), MAX, MAX),
+            (n(1), MAX, MAX),
+            (n(1), MIN, MIN | (MIN >> 1)),
+            (n(4), neg(16), neg(1)),
+            (n(4), neg(17), neg(2)),
+            (n(255), MIN, MAX),
+            (n(256), MIN, MAX),
+            (MAX, MIN, MAX),
+            (n(254), MIN - 1, n(1)),
+            (n(255), MIN - 1, n(0)),
+            (n(256), MIN - 1, n(0)),
+            (MAX, n(1), n(0)),
+        ]);
+    }
+}
@@ -1,19 +1,288 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 use ethnum::{U256};
-use std::collections::HashMap;
+use sha3::{Digest, Keccak256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::bloom::Bloom;
+use crate::context;
+use crate::eof;
+use crate::evm;
+use crate::hardfork::Hardfork;
+use crate::proof::{AccountProof, StorageProof};
+use crate::receipt::Receipt;
+use crate::requests::{self, ConsolidationRequest, DepositRequest, Requests, WithdrawalRequest};
+use crate::rlp::{self, Fields, RlpError, RlpStruct};
+use crate::transaction::{Authorization, ExecutionResult, GAS_PER_BLOB, PER_AUTH_BASE_COST, PER_EMPTY_ACCOUNT_COST, Transaction, TransactionError, TxType};
+use crate::trie::{self, Trie};
+use crate::utils;
+
+
+// refund for destroying a contract, removed by EIP-3529
+pub const SELFDESTRUCT_REFUND: u64 = 24000;
+
+// seconds between two consecutive blocks on the beacon chain
+pub const SLOT_TIME: u64 = 12;
+
+// only the last 256 ancestors are reachable through BLOCKHASH
+pub const BLOCKHASH_WINDOW: u64 = 256;
+
+// EIP-1559: base fee of the first London block, its largest change per block
+// as a denominator and the gas limit to gas target ratio
+pub const INITIAL_BASE_FEE: u64 = 1_000_000_000;
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
+// a block includes at most two ommers, at most six generations back
+pub const MAX_OMMERS: usize = 2;
+pub const MAX_OMMER_DEPTH: u64 = 6;
 
+// withdrawal amounts are in gwei
+pub const GWEI: u64 = 1_000_000_000;
 
-#[derive(Copy, Clone)]
+// EIP-4844: lowest price of a unit of blob gas
+pub const MIN_BASE_FEE_PER_BLOB_GAS: u64 = 1;
+
+// caller of the system contracts the protocol runs at the start of a block,
+// with gas that is neither charged nor counted towards the block
+pub const SYSTEM_ADDRESS: U256 = U256::from_words(0xffffffff, 0xfffffffffffffffffffffffffffffffe);
+pub const SYSTEM_CALL_GAS: u64 = 30_000_000;
+
+// EIP-4788: ring buffer of parent beacon block roots, keyed by timestamp
+pub const BEACON_ROOTS_ADDRESS: U256 = U256::from_words(0x000f3df6, 0xd732807ef1319fb7b8bb8522d0beac02);
+pub const BEACON_ROOTS_CODE: &str = "3373fffffffffffffffffffffffffffffffffffffffe14604d57602036146024575f5ffd5b5f35801560495762001fff810690815414603c575f5ffd5b62001fff01545f5260205ff35b5f5ffd5b62001fff42064281555f359062001fff015500";
+
+// EIP-2935: ring buffer of the last 8191 block hashes, keyed by number
+pub const HISTORY_STORAGE_ADDRESS: U256 = U256::from_words(0x0000f908, 0x27f1c53a10cb7a02335b175320002935);
+pub const HISTORY_STORAGE_CODE: &str = "3373fffffffffffffffffffffffffffffffffffffffe14604657602036036042575f35600143038111604257611fff81430311604257611fff9006545f5260205ff35b5f5ffd5b5f35611fff60014303065500";
+
+// EIP-7702: code of an EOA delegating to `address` is 0xef0100 || address
+pub const DELEGATION_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+// keccak256(rlp([])), the ommers hash of every post-merge block
+pub const EMPTY_OMMERS_HASH: U256 = U256::from_words(
+    0x1dcc4de8dec75d7aab85b567b6ccd41a,
+    0xd312451b948a7413f0a142fd40d49347
+);
+
+// keccak256(rlp("")), the root of an empty trie
+pub const EMPTY_ROOT_HASH: U256 = U256::from_words(
+    0x56e81f171bcc55a6ff8345e692c0f86e,
+    0x5b48e01b996cadc001622fb5e363b421
+);
+
+#[derive(Clone)]
 pub struct Block {
-    pub blockhash: U256,
+    pub parent_hash: U256,
+    pub ommers_hash: U256,
     pub coinbase: U256,
-    pub timestamp: U256,
-    pub number: U256,
+    pub state_root: U256,
+    pub transactions_root: U256,
+    pub receipts_root: U256,
+    pub logs_bloom: Bloom,
+    // zero post-merge, the proof of work target before
     pub difficulty: U256,
+    pub number: U256,
     pub gaslimit: U256,
+    pub gas_used: U256,
+    pub timestamp: U256,
+    pub extra_data: Vec<u8>,
+    // the mix hash slot, post-merge it carries the beacon chain randomness (EIP-4399)
+    pub prevrandao: U256,
+    pub nonce: u64,
+    // header fields added by later forks are absent on older blocks
+    pub basefee: Option<U256>,
+    pub withdrawals_root: Option<U256>,
+    pub blob_gas_used: Option<U256>,
+    pub excess_blob_gas: Option<U256>,
+    pub parent_beacon_block_root: Option<U256>,
+    pub requests_hash: Option<U256>,
+    // not part of the header, the chain the block belongs to
     pub chainid: U256,
-    pub basefee: U256,
 }
 
+impl Block {
+    pub fn genesis() -> Block {
+        return Block {
+            parent_hash: U256::new(0),
+            ommers_hash: EMPTY_OMMERS_HASH,
+            coinbase: U256::new(0),
+            state_root: EMPTY_ROOT_HASH,
+            transactions_root: EMPTY_ROOT_HASH,
+            receipts_root: EMPTY_ROOT_HASH,
+            logs_bloom: Bloom::new(),
+            difficulty: U256::new(0),
+            number: U256::new(0),
+            gaslimit: U256::new(30_000_000),
+            gas_used: U256::new(0),
+            timestamp: U256::new(0),
+            extra_data: Vec::new(),
+            prevrandao: U256::new(0),
+            nonce: 0,
+            basefee: Some(U256::from(INITIAL_BASE_FEE)),
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            requests_hash: None,
+            chainid: U256::new(1)
+        };
+    }
+
+    // child block inheriting the environment, `seconds` later than the parent.
+    // prevrandao is derived from the parent's so runs stay deterministic
+    pub fn child(&self, seconds: u64) -> Block {
+        let mut keccak256 = Keccak256::new();
+        keccak256.update(self.prevrandao.to_be_bytes());
+
+        return Block {
+            parent_hash: self.hash(),
+            ommers_hash: EMPTY_OMMERS_HASH,
+            coinbase: self.coinbase,
+            state_root: self.state_root,
+            transactions_root: EMPTY_ROOT_HASH,
+            receipts_root: EMPTY_ROOT_HASH,
+            logs_bloom: Bloom::new(),
+            difficulty: self.difficulty,
+            number: self.number + 1,
+            gaslimit: self.gaslimit,
+            gas_used: U256::new(0),
+            timestamp: self.timestamp + U256::from(seconds),
+            extra_data: Vec::new(),
+            prevrandao: U256::from_be_bytes(keccak256.finalize().into()),
+            nonce: 0,
+            basefee: self.basefee,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            requests_hash: None,
+            chainid: self.chainid
+        };
+    }
+
+    // EIP-1559: the base fee moves by up to 1/8 per block, towards blocks
+    // using exactly their gas target
+    pub fn next_basefee(&self) -> U256 {
+        let basefee: U256 = match self.basefee {
+            Some(basefee) => basefee,
+            None => return U256::from(INITIAL_BASE_FEE)
+        };
+        let target: U256 = self.gaslimit / U256::from(ELASTICITY_MULTIPLIER);
+        let denominator: U256 = U256::from(BASE_FEE_MAX_CHANGE_DENOMINATOR);
+        if target == 0 || self.gas_used == target {
+            return basefee;
+        }
+
+        if self.gas_used > target {
+            let delta: U256 = basefee * (self.gas_used - target) / target / denominator;
+            return basefee + delta.max(U256::new(1));
+        }
+        let delta: U256 = basefee * (target - self.gas_used) / target / denominator;
+        return basefee - delta;
+    }
+
+    // EIP-4844: the blob base fee grows exponentially with the blob gas used
+    // above target by the previous blocks
+    pub fn blob_basefee(&self, hardfork: Hardfork) -> U256 {
+        return utils::fake_exponential(
+            U256::from(MIN_BASE_FEE_PER_BLOB_GAS),
+            self.excess_blob_gas.unwrap_or_default(),
+            U256::from(hardfork.blob_base_fee_update_fraction())
+        );
+    }
+
+    pub fn next_excess_blob_gas(&self, hardfork: Hardfork) -> U256 {
+        let target: U256 = U256::from(GAS_PER_BLOB * hardfork.target_blobs_per_block());
+        let total: U256 = self.excess_blob_gas.unwrap_or_default() + self.blob_gas_used.unwrap_or_default();
+        return total.saturating_sub(target);
+    }
+
+    pub fn decode(raw: &[u8]) -> Result<Block, RlpError> {
+        return rlp::decode_as(raw);
+    }
+
+    pub fn hash(&self) -> U256 {
+        return utils::keccak256(&rlp::encode(self));
+    }
+}
+
+impl Default for Block {
+    fn default() -> Block {
+        return Block::genesis();
+    }
+}
+
+// the header, fields in consensus order
+impl RlpStruct for Block {
+    fn encode_fields(&self) -> Vec<Vec<u8>> {
+        let mut fields: Vec<Vec<u8>> = vec![
+            rlp::encode_hash(self.parent_hash),
+            rlp::encode_hash(self.ommers_hash),
+            rlp::encode_address(self.coinbase),
+            rlp::encode_hash(self.state_root),
+            rlp::encode_hash(self.transactions_root),
+            rlp::encode_hash(self.receipts_root),
+            rlp::encode_bytes(&self.logs_bloom.0),
+            rlp::encode_u256(self.difficulty),
+            rlp::encode_u256(self.number),
+            rlp::encode_u256(self.gaslimit),
+            rlp::encode_u256(self.gas_used),
+            rlp::encode_u256(self.timestamp),
+            rlp::encode_bytes(&self.extra_data),
+            rlp::encode_hash(self.prevrandao),
+            rlp::encode_bytes(&self.nonce.to_be_bytes()),
+        ];
+        if let Some(basefee) = self.basefee {
+            fields.push(rlp::encode_u256(basefee));
+        }
+        if let Some(withdrawals_root) = self.withdrawals_root {
+            fields.push(rlp::encode_hash(withdrawals_root));
+        }
+        if let Some(blob_gas_used) = self.blob_gas_used {
+            fields.push(rlp::encode_u256(blob_gas_used));
+        }
+        if let Some(excess_blob_gas) = self.excess_blob_gas {
+            fields.push(rlp::encode_u256(excess_blob_gas));
+        }
+        if let Some(parent_beacon_block_root) = self.parent_beacon_block_root {
+            fields.push(rlp::encode_hash(parent_beacon_block_root));
+        }
+        if let Some(requests_hash) = self.requests_hash {
+            fields.push(rlp::encode_hash(requests_hash));
+        }
+        return fields;
+    }
+
+    fn decode_fields(fields: &mut Fields) -> Result<Block, RlpError> {
+        let mut block: Block = Block::genesis();
+        block.parent_hash = fields.next_hash()?;
+        block.ommers_hash = fields.next_hash()?;
+        block.coinbase = fields.next_address()?;
+        block.state_root = fields.next_hash()?;
+        block.transactions_root = fields.next_hash()?;
+        block.receipts_root = fields.next_hash()?;
+        block.logs_bloom = Bloom(fields.next_item()?.fixed_bytes(256)?.try_into().unwrap());
+        block.difficulty = fields.next_value()?;
+        block.number = fields.next_value()?;
+        block.gaslimit = fields.next_value()?;
+        block.gas_used = fields.next_value()?;
+        block.timestamp = fields.next_value()?;
+        block.extra_data = fields.next_bytes()?;
+        block.prevrandao = fields.next_hash()?;
+        block.nonce = u64::from_be_bytes(fields.next_item()?.fixed_bytes(8)?.try_into().unwrap());
+        block.basefee = if fields.remaining() > 0 { Some(fields.next_value()?) } else { None };
+        block.withdrawals_root = if fields.remaining() > 0 { Some(fields.next_hash()?) } else { None };
+        block.blob_gas_used = if fields.remaining() > 0 { Some(fields.next_value()?) } else { None };
+        block.excess_blob_gas = if fields.remaining() > 0 { Some(fields.next_value()?) } else { None };
+        block.parent_beacon_block_root = if fields.remaining() > 0 { Some(fields.next_hash()?) } else { None };
+        block.requests_hash = if fields.remaining() > 0 { Some(fields.next_hash()?) } else { None };
+        return Ok(block);
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct Account {
     pub nonce: U256,
     pub balance: U256,
@@ -21,32 +290,268 @@ pub struct Account {
     pub code: Vec<u8>
 }
 
-struct Log {
-    pub data: Vec<u8>,
-    pub topic1: U256,
-    pub topic2: U256,
-    pub topic3: U256,
-    pub topic4: U256
+pub fn delegation_designator(address: U256) -> Vec<u8> {
+    let mut code: Vec<u8> = DELEGATION_PREFIX.to_vec();
+    code.extend(&address.to_be_bytes()[12..32]);
+    return code;
+}
+
+// the address `code` delegates to, if it is a delegation designator
+pub fn delegated_address(code: &[u8]) -> Option<U256> {
+    if code.len() != 23 || code[0..3] != DELEGATION_PREFIX {
+        return None;
+    }
+    let mut word: [u8; 32] = [0; 32];
+    word[12..32].copy_from_slice(&code[3..23]);
+    return Some(U256::from_be_bytes(word));
+}
+
+impl Account {
+    pub fn is_empty(&self) -> bool {
+        return self.nonce == 0 && self.balance == 0 && self.code.is_empty();
+    }
+
+    pub fn code_hash(&self) -> U256 {
+        return utils::keccak256(&self.code);
+    }
+
+    // storage trie keyed by keccak256(slot), zero slots are not part of it
+    pub fn storage_trie(&self) -> Trie {
+        let mut trie: Trie = Trie::new();
+        for (slot, value) in &self.storage {
+            if *value != 0 {
+                trie.insert(&utils::keccak256(&slot.to_be_bytes()).to_be_bytes(), rlp::encode_u256(*value));
+            }
+        }
+        return trie;
+    }
+
+    pub fn storage_root(&self) -> U256 {
+        return self.storage_trie().root_hash();
+    }
+
+    // leaf of the state trie: rlp([nonce, balance, storage_root, code_hash])
+    pub fn encode_state(&self) -> Vec<u8> {
+        return rlp::encode_list(&[
+            rlp::encode_u256(self.nonce),
+            rlp::encode_u256(self.balance),
+            rlp::encode_hash(self.storage_root()),
+            rlp::encode_hash(self.code_hash()),
+        ]);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Log {
+    pub address: U256,
+    pub topics: Vec<U256>,
+    pub data: Vec<u8>
+}
+
+impl Log {
+    pub fn new(address: U256, topics: Vec<U256>, data: Vec<u8>) -> Log {
+        return Log {
+            address: address,
+            topics: topics,
+            data: data
+        };
+    }
+
+}
+
+impl RlpStruct for Log {
+    fn encode_fields(&self) -> Vec<Vec<u8>> {
+        let topics: Vec<Vec<u8>> = self.topics.iter().map(|topic| rlp::encode_hash(*topic)).collect();
+        return vec![
+            rlp::encode_address(self.address),
+            rlp::encode_list(&topics),
+            rlp::encode_bytes(&self.data),
+        ];
+    }
+
+    fn decode_fields(fields: &mut Fields) -> Result<Log, RlpError> {
+        let address: U256 = fields.next_address()?;
+        let topics: Vec<U256> = fields.next_item()?.list()?.iter()
+            .map(|topic| topic.hash())
+            .collect::<Result<Vec<U256>, RlpError>>()?;
+        let data: Vec<u8> = fields.next_bytes()?;
+        return Ok(Log::new(address, topics, data));
+    }
+}
+
+
+// EIP-4895: ether moved from the beacon chain to an execution layer account,
+// outside of the EVM and without costing any gas
+#[derive(Clone, Debug, PartialEq)]
+pub struct Withdrawal {
+    pub index: u64,
+    pub validator_index: u64,
+    pub address: U256,
+    // in gwei
+    pub amount: u64
+}
+
+impl Withdrawal {
+    pub fn new(index: u64, validator_index: u64, address: U256, amount: u64) -> Withdrawal {
+        return Withdrawal {
+            index: index,
+            validator_index: validator_index,
+            address: address,
+            amount: amount
+        };
+    }
+}
+
+impl RlpStruct for Withdrawal {
+    fn encode_fields(&self) -> Vec<Vec<u8>> {
+        return vec![
+            rlp::encode_u64(self.index),
+            rlp::encode_u64(self.validator_index),
+            rlp::encode_address(self.address),
+            rlp::encode_u64(self.amount),
+        ];
+    }
+
+    fn decode_fields(fields: &mut Fields) -> Result<Withdrawal, RlpError> {
+        return Ok(Withdrawal {
+            index: fields.next_value()?,
+            validator_index: fields.next_value()?,
+            address: fields.next_address()?,
+            amount: fields.next_value()?
+        });
+    }
+}
+
+
+// reasons a block cannot be built on the current chain
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockError {
+    WithdrawalsNotSupported,
+    OmmersNotSupported,
+    TooManyOmmers,
+    InvalidOmmer { number: U256 },
+    DuplicateOmmer { hash: U256 },
+    // a request contract reverted or ran out of gas, which makes the block invalid (EIP-7002, EIP-7251)
+    SystemCallFailed { address: U256 }
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockError::WithdrawalsNotSupported => write!(f, "withdrawals before Shanghai"),
+            BlockError::OmmersNotSupported => write!(f, "ommers after the merge"),
+            BlockError::TooManyOmmers => write!(f, "more than {} ommers", MAX_OMMERS),
+            BlockError::InvalidOmmer { number } => write!(f, "ommer {} is not a recent ancestor's sibling", number),
+            BlockError::DuplicateOmmer { hash } => write!(f, "ommer {:#x} is already included", hash),
+            BlockError::SystemCallFailed { address } => write!(f, "system call to {:#x} failed", address)
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+
+// state as it was before a call frame, restored when the frame fails
+pub struct Snapshot {
+    accounts: HashMap<U256, Account>,
+    logs: usize,
+    destroy_list: usize,
+    created: HashSet<U256>,
+    touched: HashSet<U256>,
+    refund: U256
+}
+
+
+// outcome of building a block, one result per transaction offered. Invalid
+// transactions are left out of the block
+pub struct BlockResult {
+    pub block: Block,
+    pub results: Vec<Result<ExecutionResult, TransactionError>>,
+    pub requests: Requests
 }
 
 
 pub struct BlockChain {
+    hardfork: Hardfork,
+    // EOF (EIP-7692) is not scheduled for any fork, it is switched on separately
+    eof: bool,
     blocks: Vec<Block>,
     accounts: HashMap<U256, Account>,
+    // transactions included in the current block, their receipts, the withdrawals and the ommers
+    transactions: Vec<Transaction>,
+    receipts: Vec<Receipt>,
+    withdrawals: Vec<Withdrawal>,
+    ommers: Vec<Block>,
+    // hashes of the ommers included by sealed blocks
+    included_ommers: HashSet<U256>,
+    // whether the system calls opening the current block already ran
+    block_started: bool,
+    // requests of the last sealed block
+    requests: Requests,
     logs: Vec<Log>,
-    destroy_list: Vec<U256>
+    // transaction scoped: contracts to delete, contracts created, accounts
+    // touched (EIP-161) and the gas refund counter
+    destroy_list: Vec<U256>,
+    created: HashSet<U256>,
+    touched: HashSet<U256>,
+    refund: U256
+}
+
+impl Default for BlockChain {
+    fn default() -> BlockChain {
+        return BlockChain::new();
+    }
 }
 
 impl BlockChain {
     pub fn new() -> BlockChain {
+        return BlockChain::with_genesis(Block::genesis());
+    }
+
+    pub fn with_genesis(genesis: Block) -> BlockChain {
         return BlockChain {
-            blocks: Vec::new(),
+            hardfork: Hardfork::default(),
+            eof: false,
+            blocks: vec![genesis],
             accounts: HashMap::new(),
+            transactions: Vec::new(),
+            receipts: Vec::new(),
+            withdrawals: Vec::new(),
+            ommers: Vec::new(),
+            included_ommers: HashSet::new(),
+            block_started: false,
+            requests: Requests::new(),
             logs: Vec::new(),
-            destroy_list: Vec::new()
+            destroy_list: Vec::new(),
+            created: HashSet::new(),
+            touched: HashSet::new(),
+            refund: U256::new(0)
         };
     }
 
+    pub fn get_hardfork(&self) -> Hardfork {
+        return self.hardfork;
+    }
+
+    // switching to London makes the current block the fork block, which
+    // starts at the initial base fee (EIP-1559)
+    pub fn set_hardfork(&mut self, hardfork: Hardfork) {
+        self.hardfork = hardfork;
+        let mut block: Block = self.get_current_block();
+        if hardfork.is_enabled(Hardfork::London) && block.basefee.is_none() {
+            block.basefee = Some(U256::from(INITIAL_BASE_FEE));
+            self.set_block(block);
+        }
+    }
+
+    pub fn is_eof_enabled(&self) -> bool {
+        return self.eof;
+    }
+
+    pub fn set_eof_enabled(&mut self, enabled: bool) {
+        self.eof = enabled;
+    }
+
     pub fn add_contract(&mut self, address: &str, bytecode: Vec<u8>) {
         let account = Account {
             nonce: U256::new(0),
@@ -73,83 +578,1787 @@ impl BlockChain {
         );
     }
 
-    pub fn register_to_destroy(&mut self, address: U256, recipient: U256) {
-        let mut contract_account = self.accounts.get_mut(&address).unwrap();
-        let mut balance = contract_account.balance;
-        contract_account.balance = U256::new(0);
+    pub fn has_account(&self, address: U256) -> bool {
+        return self.accounts.contains_key(&address);
+    }
+
+    // missing, or without nonce, balance and code (EIP-161)
+    pub fn is_empty_account(&self, address: U256) -> bool {
+        match self.accounts.get(&address) {
+            Some(account) => account.is_empty(),
+            None => true
+        }
+    }
+
+    // creates an empty account, an existing one is left as it is
+    pub fn create_account(&mut self, address: U256) {
+        self.accounts.entry(address).or_default();
+    }
+
+    pub fn fund(&mut self, address: U256, amount: U256) {
+        self.get_account(address).balance += amount;
+    }
+
+    pub fn set_balance(&mut self, address: U256, balance: U256) {
+        self.get_account(address).balance = balance;
+    }
+
+    pub fn set_nonce(&mut self, address: U256, nonce: U256) {
+        self.get_account(address).nonce = nonce;
+    }
+
+    pub fn set_code(&mut self, address: U256, code: Vec<u8>) {
+        self.get_account(address).code = code;
+    }
 
-        let mut recipient_account = self.accounts.get_mut(&recipient).unwrap();
-        recipient_account.balance = balance;
-        self.destroy_list.push(address);
+    pub fn set_storage(&mut self, address: U256, key: U256, value: U256) {
+        self.get_account(address).storage.insert(key, value);
     }
 
-    pub fn clean_contracts(&mut self) {
+    // reads never create accounts, a missing one is empty
+    pub fn get_balance(&self, address: U256) -> U256 {
+        match self.accounts.get(&address) {
+            Some(account) => account.balance,
+            None => U256::new(0)
+        }
+    }
+
+    pub fn get_nonce(&self, address: U256) -> U256 {
+        match self.accounts.get(&address) {
+            Some(account) => account.nonce,
+            None => U256::new(0)
+        }
+    }
+
+    pub fn get_storage(&self, address: U256, key: U256) -> U256 {
+        match self.accounts.get(&address) {
+            Some(account) => account.storage.get(&key).copied().unwrap_or_default(),
+            None => U256::new(0)
+        }
+    }
+
+    // code stored at `address`, as the EXTCODE* family sees it
+    pub fn get_code(&self, address: U256) -> Vec<u8> {
+        match self.accounts.get(&address) {
+            Some(account) => account.code.clone(),
+            None => Vec::new()
+        }
+    }
+
+    // code run when `address` is called. A delegated EOA runs the code of
+    // its delegate, only one hop is followed (EIP-7702)
+    pub fn get_executable_code(&self, address: U256) -> Vec<u8> {
+        let code: Vec<u8> = self.get_code(address);
+        match delegated_address(&code) {
+            Some(delegate) => self.get_code(delegate),
+            None => code
+        }
+    }
+
+    pub fn transfer(&mut self, from: U256, to: U256, value: U256) -> bool {
+        if self.get_balance(from) < value {
+            return false;
+        }
+        self.touch(from);
+        self.touch(to);
+        self.get_account(from).balance -= value;
+        self.get_account(to).balance += value;
+        return true;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        return Snapshot {
+            accounts: self.accounts.clone(),
+            logs: self.logs.len(),
+            destroy_list: self.destroy_list.len(),
+            created: self.created.clone(),
+            touched: self.touched.clone(),
+            refund: self.refund
+        };
+    }
+
+    pub fn revert(&mut self, snapshot: Snapshot) {
+        self.accounts = snapshot.accounts;
+        self.logs.truncate(snapshot.logs);
+        self.destroy_list.truncate(snapshot.destroy_list);
+        self.created = snapshot.created;
+        self.touched = snapshot.touched;
+        self.refund = snapshot.refund;
+    }
+
+    pub fn mark_created(&mut self, address: U256) {
+        self.created.insert(address);
+    }
+
+    // calls and value transfers touch accounts, bringing them into existence.
+    // Since Spurious Dragon touched accounts left empty are deleted (EIP-161)
+    pub fn touch(&mut self, address: U256) {
+        self.create_account(address);
+        self.touched.insert(address);
+    }
+
+    pub fn get_refund(&self) -> U256 {
+        return self.refund;
+    }
+
+    // moves the balance to the beneficiary and schedules the deletion. Since
+    // Cancun (EIP-6780) only contracts created in the same transaction are deleted
+    pub fn selfdestruct(&mut self, address: U256, beneficiary: U256) {
+        let balance: U256 = self.get_account(address).balance;
+        self.get_account(address).balance = U256::new(0);
+        self.touch(beneficiary);
+        self.get_account(beneficiary).balance += balance;
+
+        let deletes: bool = !self.hardfork.is_enabled(Hardfork::Cancun) || self.created.contains(&address);
+        if !deletes {
+            return;
+        }
+
+        if !self.destroy_list.contains(&address) {
+            if !self.hardfork.is_enabled(Hardfork::London) {
+                self.refund += U256::from(SELFDESTRUCT_REFUND);
+            }
+            self.destroy_list.push(address);
+        }
+        // destroying into itself burns the balance
+        self.get_account(address).balance = U256::new(0);
+    }
+
+    // end of transaction cleanup of the transaction scoped state, the logs
+    // move into the receipt and the receipt into the current block
+    pub fn finalize_transaction(&mut self, tx_type: TxType, status: bool, gas_used: U256) {
+        let mut logs: Vec<Log> = std::mem::take(&mut self.logs);
+        if !status {
+            logs.clear();
+        }
+        let cumulative_gas_used: U256 = match self.receipts.last() {
+            Some(receipt) => receipt.cumulative_gas_used + gas_used,
+            None => gas_used
+        };
+
         for contract in &self.destroy_list {
             self.accounts.remove(contract);
         }
+        if self.hardfork.is_enabled(Hardfork::SpuriousDragon) {
+            for address in &self.touched {
+                if self.accounts.get(address).is_some_and(|account| account.is_empty()) {
+                    self.accounts.remove(address);
+                }
+            }
+        }
+        self.destroy_list.clear();
+        self.created.clear();
+        self.touched.clear();
+        self.refund = U256::new(0);
+
+        let mut receipt: Receipt = Receipt::new(tx_type, status, cumulative_gas_used, logs);
+        if !self.hardfork.is_enabled(Hardfork::Byzantium) {
+            receipt.post_state = Some(self.state_root());
+        }
+        let last = self.blocks.len() - 1;
+        self.blocks[last].logs_bloom.accrue_bloom(&receipt.logs_bloom);
+        self.blocks[last].gas_used = cumulative_gas_used;
+        self.receipts.push(receipt);
+    }
+
+    // state trie keyed by keccak256(address)
+    pub fn state_trie(&self) -> Trie {
+        let mut trie: Trie = Trie::new();
+        for (address, account) in &self.accounts {
+            let key: U256 = utils::keccak256(&address.to_be_bytes()[12..32]);
+            trie.insert(&key.to_be_bytes(), account.encode_state());
+        }
+        return trie;
+    }
+
+    pub fn state_root(&self) -> U256 {
+        return self.state_trie().root_hash();
     }
 
+    // eth_getProof: the account and the requested storage slots, proven
+    // against the current state root
+    pub fn get_proof(&self, address: U256, slots: &[U256]) -> AccountProof {
+        let account: Account = self.accounts.get(&address).cloned().unwrap_or_default();
+
+        let key: U256 = utils::keccak256(&address.to_be_bytes()[12..32]);
+        let storage: Trie = account.storage_trie();
+        let storage_proof: Vec<StorageProof> = slots.iter().map(|slot| {
+            let slot_key: U256 = utils::keccak256(&slot.to_be_bytes());
+            return StorageProof {
+                key: *slot,
+                value: account.storage.get(slot).copied().unwrap_or_default(),
+                proof: storage.prove(&slot_key.to_be_bytes())
+            };
+        }).collect();
+
+        return AccountProof {
+            address: address,
+            nonce: account.nonce,
+            balance: account.balance,
+            storage_hash: storage.root_hash(),
+            code_hash: account.code_hash(),
+            account_proof: self.state_trie().prove(&key.to_be_bytes()),
+            storage_proof: storage_proof
+        };
+    }
+
+    // the account at `address` for writing, created empty when missing
     pub fn get_account(&mut self, address: U256) -> &mut Account {
-        let mut account = self.accounts.get_mut(&address).unwrap();
-        return account;
+        return self.accounts.entry(address).or_default();
     }
 
     pub fn get_current_block(&self) -> Block {
-        return self.blocks[self.blocks.len() - 1];
+        return self.blocks[self.blocks.len() - 1].clone();
+    }
+
+    pub fn get_block(&self, number: U256) -> Option<Block> {
+        return self.blocks.iter().find(|block| block.number == number).cloned();
+    }
+
+    // hash of an ancestor of the current block, zero outside the BLOCKHASH window
+    pub fn get_block_hash(&self, number: U256) -> U256 {
+        let current: U256 = self.get_current_block().number;
+        if number >= current || current - number > U256::from(BLOCKHASH_WINDOW) {
+            return U256::new(0);
+        }
+
+        match self.get_block(number) {
+            Some(block) => block.hash(),
+            None => U256::new(0)
+        }
+    }
+
+    // replaces the environment of the block currently being executed
+    pub fn set_block(&mut self, block: Block) {
+        let last = self.blocks.len() - 1;
+        self.blocks[last] = block;
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: U256) {
+        let mut block = self.get_current_block();
+        block.timestamp = timestamp;
+        self.set_block(block);
     }
 
-    pub fn add_log0(&mut self, data: Vec<u8>) {
-        let log: Log = Log{
-            data: data,
-            topic1: U256::new(0),
-            topic2: U256::new(0),
-            topic3: U256::new(0),
-            topic4: U256::new(0)
+    pub fn set_block_number(&mut self, number: U256) {
+        let mut block = self.get_current_block();
+        block.number = number;
+        self.set_block(block);
+    }
+
+    pub fn set_coinbase(&mut self, coinbase: U256) {
+        let mut block = self.get_current_block();
+        block.coinbase = coinbase;
+        self.set_block(block);
+    }
+
+    pub fn set_prevrandao(&mut self, prevrandao: U256) {
+        let mut block = self.get_current_block();
+        block.prevrandao = prevrandao;
+        self.set_block(block);
+    }
+
+    // has to be set before the block's first transaction, which is when it
+    // gets written to the beacon roots contract
+    pub fn set_parent_beacon_block_root(&mut self, root: U256) {
+        let mut block = self.get_current_block();
+        block.parent_beacon_block_root = Some(root);
+        self.set_block(block);
+    }
+
+    // installs the EIP-4788 and EIP-2935 contracts, which are only called
+    // by the protocol once they exist. The EIP-7002 and EIP-7251 request
+    // contracts are not bundled, see deploy_request_contracts
+    pub fn deploy_system_contracts(&mut self) {
+        self.add_contract_bin(BEACON_ROOTS_ADDRESS, hex::decode(BEACON_ROOTS_CODE).unwrap());
+        self.add_contract_bin(HISTORY_STORAGE_ADDRESS, hex::decode(HISTORY_STORAGE_CODE).unwrap());
+        self.get_account(BEACON_ROOTS_ADDRESS).nonce = U256::new(1);
+        self.get_account(HISTORY_STORAGE_ADDRESS).nonce = U256::new(1);
+    }
+
+    // installs the withdrawal (EIP-7002) and consolidation (EIP-7251) request
+    // contracts at their spec addresses. At the end of every Prague block each
+    // is called from SYSTEM_ADDRESS with empty calldata and has to return the
+    // requests it dequeued back to back, in the layout of the request types.
+    // The bytecode deployed on mainnet is not bundled: the request fee and
+    // the queue limits of those EIPs are whatever the given code implements
+    pub fn deploy_request_contracts(&mut self, withdrawal_code: Vec<u8>, consolidation_code: Vec<u8>) {
+        self.add_contract_bin(requests::WITHDRAWAL_REQUEST_ADDRESS, withdrawal_code);
+        self.add_contract_bin(requests::CONSOLIDATION_REQUEST_ADDRESS, consolidation_code);
+        self.get_account(requests::WITHDRAWAL_REQUEST_ADDRESS).nonce = U256::new(1);
+        self.get_account(requests::CONSOLIDATION_REQUEST_ADDRESS).nonce = U256::new(1);
+    }
+
+    // calls a system contract as SYSTEM_ADDRESS outside of any transaction:
+    // no gas is paid, nothing counts towards the block and the caller is not
+    // left behind in the state. Returns None when there is no contract to call
+    pub fn system_call(&mut self, address: U256, calldata: Vec<u8>) -> Option<evm::FrameResult> {
+        if self.get_code(address).is_empty() {
+            return None;
+        }
+
+        let caller_exists: bool = self.has_account(SYSTEM_ADDRESS);
+        self.create_account(SYSTEM_ADDRESS);
+        let code: Vec<u8> = self.get_code(address);
+        let tx = context::TX::new(SYSTEM_ADDRESS, U256::new(0));
+        let msg = context::MSG::new(SYSTEM_ADDRESS, address, calldata, U256::new(0), U256::from(SYSTEM_CALL_GAS));
+        let ctx = context::CTX::new(tx, msg, address, code);
+        let result: evm::FrameResult = evm::execute_call(self, ctx, 0, U256::new(0));
+
+        if !caller_exists {
+            self.accounts.remove(&SYSTEM_ADDRESS);
+        }
+        self.logs.clear();
+        self.destroy_list.clear();
+        self.created.clear();
+        self.touched.clear();
+        self.refund = U256::new(0);
+        return Some(result);
+    }
+
+    // system calls run once per block, before its first transaction: the
+    // parent beacon block root (Cancun) and the parent hash (Prague) are stored
+    fn start_block(&mut self) {
+        if self.block_started {
+            return;
+        }
+        self.block_started = true;
+
+        let block: Block = self.get_current_block();
+        if block.number == 0 {
+            return;
+        }
+        if self.hardfork.is_enabled(Hardfork::Cancun) {
+            let root: U256 = block.parent_beacon_block_root.unwrap_or_default();
+            self.system_call(BEACON_ROOTS_ADDRESS, root.to_be_bytes().to_vec());
+        }
+        if self.hardfork.is_enabled(Hardfork::Prague) {
+            self.system_call(HISTORY_STORAGE_ADDRESS, block.parent_hash.to_be_bytes().to_vec());
+        }
+    }
+
+    // seals the current block and starts a new one on top of it
+    pub fn advance_block(&mut self) -> Result<Block, BlockError> {
+        return self.advance_blocks(1, SLOT_TIME);
+    }
+
+    pub fn advance_blocks(&mut self, count: u64, seconds_per_block: u64) -> Result<Block, BlockError> {
+        for _ in 0..count {
+            self.seal_block(seconds_per_block)?;
+        }
+        return Ok(self.get_current_block());
+    }
+
+    // commits the current block to the state and to the transactions and
+    // receipts executed in it, then starts its child `seconds` later.
+    // Returns the sealed block. A failed request system call leaves the
+    // block open and the state as it was before the request contracts ran
+    pub fn seal_block(&mut self, seconds: u64) -> Result<Block, BlockError> {
+        self.start_block();
+        if self.hardfork.is_enabled(Hardfork::Prague) {
+            let snapshot: Snapshot = self.snapshot();
+            self.requests = match self.collect_requests() {
+                Ok(requests) => requests,
+                Err(error) => {
+                    self.revert(snapshot);
+                    return Err(error);
+                }
+            };
+        } else {
+            self.requests = Requests::new();
+        }
+        self.apply_block_rewards();
+
+        let transactions: Vec<Vec<u8>> = self.transactions.iter().map(|tx| tx.encode()).collect();
+        let receipts: Vec<Vec<u8>> = self.receipts.iter().map(|receipt| receipt.encode()).collect();
+        let withdrawals: Vec<Vec<u8>> = self.withdrawals.iter().map(rlp::encode).collect();
+
+        let mut block: Block = self.get_current_block();
+        block.state_root = self.state_root();
+        block.transactions_root = trie::ordered_root(&transactions);
+        block.receipts_root = trie::ordered_root(&receipts);
+        block.ommers_hash = utils::keccak256(&rlp::encode(&self.ommers));
+        block.requests_hash = if self.hardfork.is_enabled(Hardfork::Prague) {
+            Some(self.requests.hash())
+        } else {
+            None
         };
-        self.logs.push(log);
+        block.gas_used = self.receipts.last().map(|receipt| receipt.cumulative_gas_used).unwrap_or_default();
+        if !self.hardfork.is_enabled(Hardfork::London) {
+            block.basefee = None;
+        }
+        if self.hardfork.is_enabled(Hardfork::Shanghai) {
+            block.withdrawals_root = Some(trie::ordered_root(&withdrawals));
+        }
+        if self.hardfork.is_enabled(Hardfork::Cancun) {
+            block.blob_gas_used = Some(block.blob_gas_used.unwrap_or_default());
+            block.excess_blob_gas = Some(block.excess_blob_gas.unwrap_or_default());
+            block.parent_beacon_block_root = Some(block.parent_beacon_block_root.unwrap_or_default());
+        } else {
+            block.blob_gas_used = None;
+            block.excess_blob_gas = None;
+            block.parent_beacon_block_root = None;
+        }
+        self.set_block(block.clone());
+
+        let mut child: Block = block.child(seconds);
+        child.basefee = if self.hardfork.is_enabled(Hardfork::London) {
+            Some(block.next_basefee())
+        } else {
+            None
+        };
+        if self.hardfork.is_enabled(Hardfork::Cancun) {
+            child.blob_gas_used = Some(U256::new(0));
+            child.excess_blob_gas = Some(block.next_excess_blob_gas(self.hardfork));
+        }
+        self.blocks.push(child);
+        self.transactions.clear();
+        self.receipts.clear();
+        self.withdrawals.clear();
+        self.included_ommers.extend(self.ommers.iter().map(|ommer| ommer.hash()));
+        self.ommers.clear();
+        self.block_started = false;
+        return Ok(block);
+    }
+
+    // executes `transactions` in order in the current block, applies the
+    // withdrawals and seals it. Transactions that are invalid or do not fit
+    // in the remaining gas are skipped
+    pub fn build_block(&mut self, transactions: &[Transaction], withdrawals: &[Withdrawal]) -> Result<BlockResult, BlockError> {
+        if !withdrawals.is_empty() && !self.hardfork.is_enabled(Hardfork::Shanghai) {
+            return Err(BlockError::WithdrawalsNotSupported);
+        }
+
+        let results: Vec<Result<ExecutionResult, TransactionError>> = transactions.iter()
+            .map(|tx| self.execute_transaction(tx))
+            .collect();
+        for withdrawal in withdrawals {
+            self.process_withdrawal(withdrawal);
+        }
+        let block: Block = self.seal_block(SLOT_TIME)?;
+        return Ok(BlockResult {
+            block: block,
+            results: results,
+            requests: self.requests.clone()
+        });
     }
 
-    pub fn add_log1(&mut self, data: Vec<u8>, topic1: U256) {
-        let log: Log = Log{
-            data: data,
-            topic1: topic1,
-            topic2: U256::new(0),
-            topic3: U256::new(0),
-            topic4: U256::new(0)
+    // credits the amount, converted from gwei to wei. Withdrawing nothing
+    // does not bring an account into existence
+    pub fn process_withdrawal(&mut self, withdrawal: &Withdrawal) {
+        if withdrawal.amount > 0 {
+            self.fund(withdrawal.address, U256::from(withdrawal.amount) * U256::from(GWEI));
+        }
+        self.withdrawals.push(withdrawal.clone());
+    }
+
+    // end of block requests (Prague): deposits logged by the deposit contract
+    // during the block, then whatever the withdrawal and consolidation request
+    // contracts hand out. Missing request contracts produce no requests, they
+    // have to be installed with deploy_request_contracts. A request contract
+    // that fails invalidates the block
+    fn collect_requests(&mut self) -> Result<Requests, BlockError> {
+        let mut requests: Requests = Requests::new();
+        for receipt in &self.receipts {
+            requests.deposits.extend(receipt.logs.iter().filter_map(DepositRequest::from_log));
+        }
+
+        requests.withdrawals = match self.request_call(requests::WITHDRAWAL_REQUEST_ADDRESS)? {
+            Some(output) => WithdrawalRequest::decode_all(&output),
+            None => Vec::new()
         };
-        self.logs.push(log);
+        requests.consolidations = match self.request_call(requests::CONSOLIDATION_REQUEST_ADDRESS)? {
+            Some(output) => ConsolidationRequest::decode_all(&output),
+            None => Vec::new()
+        };
+        return Ok(requests);
+    }
+
+    // output of a request contract, None when it is not deployed
+    fn request_call(&mut self, address: U256) -> Result<Option<Vec<u8>>, BlockError> {
+        match self.system_call(address, Vec::new()) {
+            Some(result) if !result.success => Err(BlockError::SystemCallFailed { address: address }),
+            Some(result) => Ok(Some(result.output)),
+            None => Ok(None)
+        }
+    }
+
+    pub fn get_requests(&self) -> &Requests {
+        return &self.requests;
     }
 
-    pub fn add_log2(&mut self, data: Vec<u8>, topic1: U256, topic2: U256) {
-        let log: Log = Log{
-            data: data,
-            topic1: topic1,
-            topic2: topic2,
-            topic3: U256::new(0),
-            topic4: U256::new(0)
+    // includes the header of a stale block in the current one, rewarding its
+    // miner. An ommer is a child of one of the last six ancestors that is not
+    // itself on the chain, and can only be included once
+    pub fn add_ommer(&mut self, ommer: Block) -> Result<(), BlockError> {
+        if self.hardfork.is_enabled(Hardfork::Paris) {
+            return Err(BlockError::OmmersNotSupported);
+        }
+        if self.ommers.len() >= MAX_OMMERS {
+            return Err(BlockError::TooManyOmmers);
+        }
+        let number: U256 = self.get_current_block().number;
+        if ommer.number >= number || number - ommer.number > U256::from(MAX_OMMER_DEPTH) {
+            return Err(BlockError::InvalidOmmer { number: ommer.number });
+        }
+
+        let hash: U256 = ommer.hash();
+        let ancestors: &[Block] = &self.blocks[..self.blocks.len() - 1];
+        let sibling: bool = ancestors.iter()
+            .any(|block| block.hash() == ommer.parent_hash && block.number + 1 == ommer.number);
+        let canonical: bool = ancestors.iter().any(|block| block.hash() == hash);
+        if !sibling || canonical {
+            return Err(BlockError::InvalidOmmer { number: ommer.number });
+        }
+        if self.included_ommers.contains(&hash) || self.ommers.iter().any(|included| included.hash() == hash) {
+            return Err(BlockError::DuplicateOmmer { hash: hash });
+        }
+        self.ommers.push(ommer);
+        return Ok(());
+    }
+
+    // pre-merge issuance: the miner gets the block reward plus 1/32 of it per
+    // ommer, each ommer's miner gets (8 - depth) / 8 of it
+    fn apply_block_rewards(&mut self) {
+        let reward: U256 = U256::from(self.hardfork.block_reward());
+        if reward == 0 {
+            return;
+        }
+
+        let block: Block = self.get_current_block();
+        let mut credits: Vec<(U256, U256)> = vec![(block.coinbase, reward + reward / 32 * U256::from(self.ommers.len() as u64))];
+        for ommer in &self.ommers {
+            credits.push((ommer.coinbase, reward * (U256::new(8) + ommer.number - block.number) / 8));
+        }
+        for (address, amount) in credits {
+            self.fund(address, amount);
+        }
+    }
+
+    pub fn get_withdrawals(&self) -> &[Withdrawal] {
+        return &self.withdrawals;
+    }
+
+    // applies a transaction on top of the current block: validation, gas purchase,
+    // execution, refunds and the coinbase tip. Invalid transactions change nothing
+    pub fn execute_transaction(&mut self, tx: &Transaction) -> Result<ExecutionResult, TransactionError> {
+        self.start_block();
+        let block: Block = self.get_current_block();
+        let sender: U256 = tx.from;
+
+        let available: U256 = block.gaslimit.saturating_sub(block.gas_used);
+        if tx.gas_limit > available {
+            return Err(TransactionError::BlockGasLimitReached { gas_limit: tx.gas_limit, available: available });
+        }
+        let intrinsic_gas: U256 = tx.intrinsic_gas(self.hardfork);
+        if tx.gas_limit < intrinsic_gas {
+            return Err(TransactionError::IntrinsicGasTooLow { required: intrinsic_gas, gas_limit: tx.gas_limit });
+        }
+        if tx.is_create() && self.hardfork.is_enabled(Hardfork::Shanghai) && tx.data.len() > evm::MAX_INITCODE_SIZE {
+            return Err(TransactionError::InitcodeTooLarge);
+        }
+        tx.validate(self.hardfork, block.chainid)?;
+        let basefee: U256 = if self.hardfork.is_enabled(Hardfork::London) {
+            block.basefee.unwrap_or_default()
+        } else {
+            U256::new(0)
+        };
+        if tx.max_fee_per_gas < basefee {
+            return Err(TransactionError::FeeCapTooLow { max_fee_per_gas: tx.max_fee_per_gas, basefee: basefee });
+        }
+        let blob_gas: U256 = tx.blob_gas();
+        let blob_basefee: U256 = block.blob_basefee(self.hardfork);
+        if tx.tx_type == TxType::Blob {
+            let max_blob_gas: U256 = U256::from(GAS_PER_BLOB * self.hardfork.max_blobs_per_block());
+            let available: U256 = max_blob_gas.saturating_sub(block.blob_gas_used.unwrap_or_default());
+            if blob_gas > available {
+                return Err(TransactionError::BlobGasLimitReached { blob_gas: blob_gas, available: available });
+            }
+            if tx.max_fee_per_blob_gas < blob_basefee {
+                return Err(TransactionError::BlobFeeCapTooLow { max_fee_per_blob_gas: tx.max_fee_per_blob_gas, blob_basefee: blob_basefee });
+            }
+        }
+
+        let account: Account = self.accounts.get(&sender).cloned().unwrap_or_default();
+        if account.nonce != tx.nonce {
+            return Err(TransactionError::NonceMismatch { expected: account.nonce, got: tx.nonce });
+        }
+        if account.nonce >= U256::from(u64::MAX) {
+            return Err(TransactionError::NonceOverflow);
+        }
+        // EIP-3607, delegated EOAs keep sending transactions
+        if !account.code.is_empty() && delegated_address(&account.code).is_none() {
+            return Err(TransactionError::SenderNotEOA);
+        }
+        let max_cost: U256 = tx.max_cost().ok_or(TransactionError::CostOverflow)?;
+        if account.balance < max_cost {
+            return Err(TransactionError::InsufficientFunds { required: max_cost, balance: account.balance });
+        }
+
+        // buy the gas upfront and bump the nonce, neither is undone if execution fails
+        let gas_price: U256 = tx.effective_gas_price(basefee);
+        self.get_account(sender).balance -= tx.gas_limit * gas_price;
+        self.get_account(sender).nonce += 1;
+
+        // blob gas is paid at the blob base fee and burned whatever the outcome
+        if blob_gas > 0 {
+            self.get_account(sender).balance -= blob_gas * blob_basefee;
+            let last = self.blocks.len() - 1;
+            self.blocks[last].blob_gas_used = Some(block.blob_gas_used.unwrap_or_default() + blob_gas);
+        }
+
+        if tx.tx_type == TxType::SetCode {
+            self.apply_authorizations(&tx.authorization_list, block.chainid);
+        }
+
+        let mut context_tx = context::TX::new(sender, gas_price);
+        context_tx.blob_hashes = tx.blob_versioned_hashes.clone();
+        let gas: U256 = tx.gas_limit - intrinsic_gas;
+        let mut contract_address: Option<U256> = None;
+        let result: evm::FrameResult = match tx.to {
+            Some(to) => {
+                let code: Vec<u8> = self.get_executable_code(to);
+                let msg = context::MSG::new(sender, to, tx.data.clone(), tx.value, gas);
+                let ctx = context::CTX::new(context_tx, msg, to, code);
+                evm::execute_call(self, ctx, 0, tx.value)
+            },
+            None => {
+                // EIP-7698: EOF init code is a container followed by its calldata
+                let (code, calldata): (Vec<u8>, Vec<u8>) = match eof::Container::decode_prefix(&tx.data) {
+                    Ok((_, size)) if self.eof => (tx.data[..size].to_vec(), tx.data[size..].to_vec()),
+                    _ => (tx.data.clone(), Vec::new())
+                };
+                let address: U256 = utils::create_address(sender, tx.nonce);
+                let msg = context::MSG::new(sender, address, calldata, tx.value, gas);
+                let ctx = context::CTX::new(context_tx, msg, address, code);
+                let result: evm::FrameResult = evm::execute_create(self, ctx, 0);
+                if result.success {
+                    contract_address = Some(address);
+                }
+                result
+            }
         };
+
+        // refunds are capped at a half of the gas used, a fifth since London (EIP-3529)
+        let mut gas_used: U256 = tx.gas_limit - result.gas_left;
+        let quotient: u64 = if self.hardfork.is_enabled(Hardfork::London) { 5 } else { 2 };
+        let refund: U256 = self.refund.min(gas_used / U256::from(quotient));
+        gas_used -= refund;
+
+        self.get_account(sender).balance += (tx.gas_limit - gas_used) * gas_price;
+
+        // the base fee part of the price is burned, it leaves the sender and is
+        // credited to nobody. Only the tip goes to the coinbase
+        let tip: U256 = gas_price - basefee;
+        self.touch(block.coinbase);
+        self.fund(block.coinbase, gas_used * tip);
+
+        self.transactions.push(tx.clone());
+        self.finalize_transaction(tx.tx_type, result.success, gas_used);
+
+        return Ok(ExecutionResult {
+            success: result.success,
+            gas_used: gas_used,
+            output: result.output,
+            contract_address: contract_address
+        });
+    }
+
+    // EIP-7702: each valid authorization points its authority's code at the
+    // delegate, or clears it for the zero address, and bumps its nonce.
+    // Invalid authorizations are skipped, they do not invalidate the transaction
+    fn apply_authorizations(&mut self, authorizations: &[Authorization], chain_id: U256) {
+        for authorization in authorizations {
+            if authorization.chain_id != 0 && authorization.chain_id != chain_id {
+                continue;
+            }
+            if authorization.nonce >= U256::from(u64::MAX) {
+                continue;
+            }
+            let authority: U256 = match authorization.recover_authority() {
+                Ok(authority) => authority,
+                Err(_) => continue
+            };
+
+            let exists: bool = self.has_account(authority);
+            if exists {
+                let account: &Account = self.get_account(authority);
+                if !account.code.is_empty() && delegated_address(&account.code).is_none() {
+                    continue;
+                }
+                if account.nonce != authorization.nonce {
+                    continue;
+                }
+                self.refund += U256::from(PER_EMPTY_ACCOUNT_COST - PER_AUTH_BASE_COST);
+            } else if authorization.nonce != 0 {
+                continue;
+            }
+
+            let account: &mut Account = self.get_account(authority);
+            account.code = if authorization.address == 0 {
+                Vec::new()
+            } else {
+                delegation_designator(authorization.address)
+            };
+            account.nonce += 1;
+        }
+    }
+
+    pub fn add_log(&mut self, log: Log) {
         self.logs.push(log);
     }
 
-    pub fn add_log3(&mut self, data: Vec<u8>, topic1: U256, topic2: U256, topic3: U256) {
-        let log: Log = Log{
-            data: data,
-            topic1: topic1,
-            topic2: topic2,
-            topic3: topic3,
-            topic4: U256::new(0)
+    // logs emitted so far by the running transaction
+    pub fn get_logs(&self) -> &[Log] {
+        return &self.logs;
+    }
+
+    pub fn get_transactions(&self) -> &[Transaction] {
+        return &self.transactions;
+    }
+
+    // receipts of the transactions executed in the current block
+    pub fn get_receipts(&self) -> &[Receipt] {
+        return &self.receipts;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // code returning `data` as its output
+    fn returning(data: &[u8]) -> Vec<u8> {
+        let size: [u8; 2] = (data.len() as u16).to_be_bytes();
+        let mut code: Vec<u8> = vec![0x61, size[0], size[1], 0x60, 0x0c, 0x5f, 0x39, 0x61, size[0], size[1], 0x5f, 0xf3];
+        code.extend(data);
+        return code;
+    }
+
+    // well known development key, funded on first use
+    const DEV_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    fn unsigned(chain: &mut BlockChain, to: Option<U256>, value: U256, data: Vec<u8>) -> Transaction {
+        let from: U256 = crate::signature::secret_key_to_address(U256::from_str_hex(DEV_KEY).unwrap()).unwrap();
+        if chain.get_balance(from) == 0 {
+            chain.fund(from, U256::new(1_000_000_000_000_000_000));
+        }
+        let mut tx: Transaction = Transaction::new(from, to, chain.get_nonce(from), U256::new(1_000_000), U256::new(2_000_000_000), value, data);
+        // replay protection (EIP-155) only exists since Spurious Dragon
+        if chain.get_hardfork().is_enabled(Hardfork::SpuriousDragon) {
+            tx.chain_id = Some(chain.get_current_block().chainid);
+        }
+        return tx;
+    }
+
+    fn signed(mut tx: Transaction) -> Transaction {
+        tx.sign(U256::from_str_hex(DEV_KEY).unwrap()).unwrap();
+        return tx;
+    }
+
+    fn execute(chain: &mut BlockChain, tx: Transaction) -> Result<ExecutionResult, TransactionError> {
+        return chain.execute_transaction(&signed(tx));
+    }
+
+    fn send(chain: &mut BlockChain, to: Option<U256>, value: U256, data: Vec<u8>) -> ExecutionResult {
+        let tx: Transaction = unsigned(chain, to, value, data);
+        return execute(chain, tx).unwrap();
+    }
+
+    #[test]
+    fn test_calls_touch_their_target() {
+        let empty: U256 = U256::new(0xdead);
+        let contract: U256 = U256::new(0x4242);
+        // CALLCODE, DELEGATECALL and STATICCALL to an existing empty account
+        let calls: [&str; 3] = ["5f5f5f5f5f61dead5af200", "5f5f5f5f61dead5af400", "5f5f5f5f61dead5afa00"];
+        for code in calls {
+            let mut chain: BlockChain = BlockChain::new();
+            chain.create_account(empty);
+            chain.add_contract_bin(contract, hex::decode(code).unwrap());
+            assert!(send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+            assert!(!chain.has_account(empty), "{}", code);
+        }
+
+        // before Spurious Dragon touched empty accounts stay
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Homestead);
+        chain.create_account(empty);
+        chain.add_contract_bin(contract, hex::decode("600060006000600061dead61fffff400").unwrap());
+        assert!(send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+        assert!(chain.has_account(empty));
+    }
+
+    #[test]
+    fn test_value_transfer() {
+        let mut chain: BlockChain = BlockChain::new();
+        let tx: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(1000), Vec::new());
+        let from: U256 = tx.from;
+        let balance: U256 = chain.get_balance(from);
+        let coinbase: U256 = chain.get_current_block().coinbase;
+
+        let result: ExecutionResult = execute(&mut chain, tx).unwrap();
+        assert!(result.success);
+        assert_eq!(result.gas_used, U256::new(21000));
+        assert_eq!(chain.get_nonce(from), U256::new(1));
+        assert_eq!(chain.get_balance(U256::new(0xbeef)), U256::new(1000));
+        let price: U256 = U256::new(2_000_000_000);
+        assert_eq!(chain.get_balance(from), balance - 1000 - result.gas_used * price);
+        // the coinbase only gets the tip, the base fee is burnt
+        let basefee: U256 = chain.get_current_block().basefee.unwrap();
+        assert_eq!(chain.get_balance(coinbase), result.gas_used * (price - basefee));
+    }
+
+    #[test]
+    fn test_intrinsic_gas() {
+        let mut chain: BlockChain = BlockChain::new();
+        let result: ExecutionResult = send(&mut chain, Some(U256::new(0xbeef)), U256::new(0), vec![0x00, 0x01]);
+        assert_eq!(result.gas_used, U256::new(21000 + 4 + 16));
+
+        let mut tx: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(0), vec![0x01]);
+        tx.gas_limit = U256::new(21015);
+        assert_eq!(execute(&mut chain, tx).err(), Some(TransactionError::IntrinsicGasTooLow {
+            required: U256::new(21016),
+            gas_limit: U256::new(21015)
+        }));
+    }
+
+    #[test]
+    fn test_invalid_transactions() {
+        let mut chain: BlockChain = BlockChain::new();
+        let mut tx: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(0), Vec::new());
+        tx.nonce = U256::new(1);
+        assert_eq!(execute(&mut chain, tx).err(), Some(TransactionError::NonceMismatch {
+            expected: U256::new(0),
+            got: U256::new(1)
+        }));
+
+        let tx: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(1_000_000_000_000_000_000), Vec::new());
+        assert!(matches!(execute(&mut chain, tx), Err(TransactionError::InsufficientFunds { .. })));
+
+        let mut tx: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(0), Vec::new());
+        tx.gas_limit = U256::new(30_000_000);
+        tx.max_fee_per_gas = U256::MAX;
+        tx.max_priority_fee_per_gas = U256::MAX;
+        assert_eq!(execute(&mut chain, tx).err(), Some(TransactionError::CostOverflow));
+
+        // nothing was charged for the rejected transactions
+        let from: U256 = crate::signature::secret_key_to_address(U256::from_str_hex(DEV_KEY).unwrap()).unwrap();
+        assert_eq!(chain.get_nonce(from), U256::new(0));
+        assert_eq!(chain.get_balance(from), U256::new(1_000_000_000_000_000_000));
+    }
+
+    #[test]
+    fn test_reverted_transaction() {
+        // stores 1 and reverts
+        let mut chain: BlockChain = BlockChain::new();
+        let contract: U256 = U256::new(0x4242);
+        chain.add_contract_bin(contract, hex::decode("600160005560006000fd").unwrap());
+        let tx: Transaction = unsigned(&mut chain, Some(contract), U256::new(1000), Vec::new());
+        let from: U256 = tx.from;
+        let balance: U256 = chain.get_balance(from);
+
+        let result: ExecutionResult = execute(&mut chain, tx).unwrap();
+        assert!(!result.success);
+        assert_eq!(chain.get_storage(contract, U256::new(0)), U256::new(0));
+        assert_eq!(chain.get_balance(contract), U256::new(0));
+        // the nonce is used and the gas paid all the same
+        assert_eq!(chain.get_nonce(from), U256::new(1));
+        assert_eq!(chain.get_balance(from), balance - result.gas_used * U256::new(2_000_000_000));
+        assert_eq!(chain.get_receipts().last().map(|receipt| receipt.status), Some(false));
+    }
+
+    #[test]
+    fn test_creation_transaction() {
+        let mut chain: BlockChain = BlockChain::new();
+        // deploys the single byte STOP
+        let tx: Transaction = unsigned(&mut chain, None, U256::new(100), hex::decode("600060005360016000f3").unwrap());
+        let from: U256 = tx.from;
+        let result: ExecutionResult = execute(&mut chain, tx).unwrap();
+        assert!(result.success);
+
+        let contract: U256 = utils::create_address(from, U256::new(0));
+        assert_eq!(result.contract_address, Some(contract));
+        assert_eq!(chain.get_code(contract), vec![0x00]);
+        assert_eq!(chain.get_nonce(contract), U256::new(1));
+        assert_eq!(chain.get_balance(contract), U256::new(100));
+        assert_eq!(chain.get_nonce(from), U256::new(1));
+
+        // the next one goes to the next address
+        let result: ExecutionResult = send(&mut chain, None, U256::new(0), hex::decode("600060005360016000f3").unwrap());
+        assert_eq!(result.contract_address, Some(utils::create_address(from, U256::new(1))));
+    }
+
+    #[test]
+    fn test_nested_calls() {
+        // calls 0x4343 with 5 wei and 0xbeef with 7, storing the outcomes in slots 0 and 1
+        let mut chain: BlockChain = BlockChain::new();
+        let caller: U256 = U256::new(0x4242);
+        let reverting: U256 = U256::new(0x4343);
+        chain.add_contract_bin(caller, hex::decode("600060006000600060056143435af16000556000600060006000600761beef5af160015500").unwrap());
+        chain.add_contract_bin(reverting, hex::decode("600160005560006000fd").unwrap());
+        chain.set_balance(caller, U256::new(100));
+
+        assert!(send(&mut chain, Some(caller), U256::new(0), Vec::new()).success);
+        // the failed call is undone, value included
+        assert_eq!(chain.get_storage(caller, U256::new(0)), U256::new(0));
+        assert_eq!(chain.get_storage(reverting, U256::new(0)), U256::new(0));
+        assert_eq!(chain.get_balance(reverting), U256::new(0));
+        assert_eq!(chain.get_storage(caller, U256::new(1)), U256::new(1));
+        assert_eq!(chain.get_balance(U256::new(0xbeef)), U256::new(7));
+        assert_eq!(chain.get_balance(caller), U256::new(93));
+    }
+
+    #[test]
+    fn test_log_data_gas() {
+        // LOG0 of 32 and of 0 bytes over memory that is already expanded
+        let mut chain: BlockChain = BlockChain::new();
+        chain.add_contract_bin(U256::new(0x4242), hex::decode("5f5f5260205fa000").unwrap());
+        chain.add_contract_bin(U256::new(0x4343), hex::decode("5f5f5260005fa000").unwrap());
+        let with_data: U256 = send(&mut chain, Some(U256::new(0x4242)), U256::new(0), Vec::new()).gas_used;
+        let without_data: U256 = send(&mut chain, Some(U256::new(0x4343)), U256::new(0), Vec::new()).gas_used;
+        assert_eq!(with_data - without_data, U256::new(32 * 8));
+
+        // data from far out of memory runs out of gas and logs nothing
+        chain.add_contract_bin(U256::new(0x4444), hex::decode("60017fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffa000").unwrap());
+        let result: ExecutionResult = send(&mut chain, Some(U256::new(0x4444)), U256::new(0), Vec::new());
+        assert!(!result.success);
+        assert_eq!(result.gas_used, U256::new(1_000_000));
+        assert!(chain.get_receipts().last().unwrap().logs.is_empty());
+    }
+
+    #[test]
+    fn test_receipts() {
+        // LOG1 of the word 0xaa with topic 0xbb, then a reverting one
+        let mut chain: BlockChain = BlockChain::new();
+        let logger: U256 = U256::new(0x4242);
+        chain.add_contract_bin(logger, hex::decode("60aa5f5260bb60205fa100").unwrap());
+        chain.add_contract_bin(U256::new(0x4343), hex::decode("60bb60205fa15f5ffd").unwrap());
+        let first: ExecutionResult = send(&mut chain, Some(logger), U256::new(0), Vec::new());
+        let second: ExecutionResult = send(&mut chain, Some(U256::new(0x4343)), U256::new(0), Vec::new());
+
+        let receipts: &[Receipt] = chain.get_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].cumulative_gas_used, first.gas_used);
+        assert_eq!(receipts[1].cumulative_gas_used, first.gas_used + second.gas_used);
+        assert_eq!(receipts[0].logs, logged(logger));
+        assert!(receipts[0].logs_bloom.contains_address(logger));
+        assert!(receipts[0].logs_bloom.contains_topic(U256::new(0xbb)));
+        // a failed transaction keeps no logs
+        assert!(!receipts[1].status);
+        assert!(receipts[1].logs.is_empty());
+        assert!(!receipts[1].logs_bloom.contains_address(U256::new(0x4343)));
+
+        // the block accumulates the gas and the blooms of its receipts
+        let bloom: Bloom = receipts[0].logs_bloom;
+        let block: Block = chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(block.gas_used, first.gas_used + second.gas_used);
+        assert_eq!(block.logs_bloom, bloom);
+    }
+
+    #[test]
+    fn test_receipt_outcome_by_fork() {
+        // before Byzantium the receipt holds the state root after the transaction
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::SpuriousDragon);
+        send(&mut chain, Some(U256::new(0xbeef)), U256::new(1), Vec::new());
+        let root: U256 = chain.state_root();
+        send(&mut chain, Some(U256::new(0xbeef)), U256::new(1), Vec::new());
+        assert_eq!(chain.get_receipts()[0].post_state, Some(root));
+        assert_eq!(chain.get_receipts()[1].post_state, Some(chain.state_root()));
+
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Byzantium);
+        send(&mut chain, Some(U256::new(0xbeef)), U256::new(1), Vec::new());
+        assert_eq!(chain.get_receipts()[0].post_state, None);
+    }
+
+    // stores COINBASE, TIMESTAMP, NUMBER, PREVRANDAO, GASLIMIT, CHAINID and
+    // BASEFEE in slots 0 to 6 and returns them
+    fn environment(chain: &mut BlockChain) -> Vec<U256> {
+        let contract: U256 = U256::new(0x4242);
+        let code: Vec<u8> = [0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x48].iter().enumerate()
+            .flat_map(|(slot, opcode)| [*opcode, 0x60, slot as u8, 0x55])
+            .collect();
+        chain.add_contract_bin(contract, code);
+        assert!(send(chain, Some(contract), U256::new(0), Vec::new()).success);
+        return (0..7).map(|slot| chain.get_storage(contract, U256::new(slot))).collect();
+    }
+
+    fn set_environment(chain: &mut BlockChain) {
+        let mut block: Block = chain.get_current_block();
+        block.coinbase = U256::new(0xc0ffee);
+        block.timestamp = U256::new(1_700_000_000);
+        block.number = U256::new(100);
+        block.difficulty = U256::new(0x1234);
+        block.prevrandao = U256::new(0x5678);
+        block.gaslimit = U256::new(25_000_000);
+        block.chainid = U256::new(5);
+        chain.set_block(block);
+    }
+
+    #[test]
+    fn test_block_environment() {
+        for (hardfork, randomness) in [(Hardfork::London, 0x1234), (Hardfork::Paris, 0x5678)] {
+            let mut chain: BlockChain = BlockChain::new();
+            chain.set_hardfork(hardfork);
+            set_environment(&mut chain);
+            // DIFFICULTY turns into PREVRANDAO with the merge (EIP-4399)
+            assert_eq!(environment(&mut chain), vec![
+                U256::new(0xc0ffee),
+                U256::new(1_700_000_000),
+                U256::new(100),
+                U256::new(randomness),
+                U256::new(25_000_000),
+                U256::new(5),
+                U256::from(INITIAL_BASE_FEE),
+            ], "{:?}", hardfork);
+        }
+    }
+
+    #[test]
+    fn test_child_environment() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Paris);
+        set_environment(&mut chain);
+        let parent: Block = chain.seal_block(12).unwrap();
+        let child: Block = chain.get_current_block();
+        assert_eq!(child.parent_hash, parent.hash());
+        assert_eq!(child.prevrandao, utils::keccak256(&parent.prevrandao.to_be_bytes()));
+
+        let values: Vec<U256> = environment(&mut chain);
+        assert_eq!(values[0], U256::new(0xc0ffee));
+        assert_eq!(values[1], U256::new(1_700_000_012));
+        assert_eq!(values[2], U256::new(101));
+        assert_eq!(values[3], child.prevrandao);
+        assert_eq!(values[4], U256::new(25_000_000));
+        assert_eq!(values[5], U256::new(5));
+        assert_eq!(values[6], parent.next_basefee());
+    }
+
+    #[test]
+    fn test_block_hash_window() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.advance_blocks(300, SLOT_TIME).unwrap();
+        let current: U256 = chain.get_current_block().number;
+        assert_eq!(current, U256::new(300));
+
+        let hash = |chain: &BlockChain, number: u64| chain.get_block(U256::from(number)).unwrap().hash();
+        assert_eq!(chain.get_block_hash(U256::new(299)), hash(&chain, 299));
+        assert_eq!(chain.get_current_block().parent_hash, hash(&chain, 299));
+        // the 256 most recent blocks, not counting the current one
+        assert_eq!(chain.get_block_hash(U256::new(44)), hash(&chain, 44));
+        assert_ne!(chain.get_block_hash(U256::new(44)), U256::new(0));
+        assert_eq!(chain.get_block_hash(U256::new(43)), U256::new(0));
+        assert_eq!(chain.get_block_hash(U256::new(0)), U256::new(0));
+        assert_eq!(chain.get_block_hash(current), U256::new(0));
+        assert_eq!(chain.get_block_hash(current + 1), U256::new(0));
+        assert_eq!(chain.get_block_hash(U256::MAX), U256::new(0));
+
+        // BLOCKHASH(NUMBER - 1) into slot 0
+        let contract: U256 = U256::new(0x4242);
+        chain.add_contract_bin(contract, hex::decode("600143034060005500").unwrap());
+        assert!(send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+        assert_eq!(chain.get_storage(contract, U256::new(0)), hash(&chain, 299));
+    }
+
+    #[test]
+    fn test_build_block() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        let logger: U256 = U256::new(0x4242);
+        chain.add_contract_bin(logger, hex::decode("60aa5f5260bb60205fa100").unwrap());
+
+        let first: Transaction = signed(unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(1000), Vec::new()));
+        let mut second: Transaction = unsigned(&mut chain, Some(logger), U256::new(0), Vec::new());
+        second.nonce = U256::new(1);
+        let second: Transaction = signed(second);
+        // reuses the first nonce, it is left out of the block
+        let mut replay: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(1), Vec::new());
+        replay.gas_limit = U256::new(30_000);
+        let replay: Transaction = signed(replay);
+        let withdrawals: Vec<Withdrawal> = vec![Withdrawal::new(0, 7, U256::new(0xaaaa), 5), Withdrawal::new(1, 8, U256::new(0xbbbb), 6)];
+
+        let result: BlockResult = chain.build_block(&[first.clone(), second.clone(), replay], &withdrawals).unwrap();
+        assert!(result.results[0].is_ok() && result.results[1].is_ok());
+        assert!(matches!(result.results[2], Err(TransactionError::NonceMismatch { .. })));
+
+        let block: Block = result.block;
+        let sealed: Block = chain.get_block(block.number).unwrap();
+        assert_eq!(sealed.hash(), block.hash());
+        assert_eq!(chain.get_current_block().parent_hash, block.hash());
+        assert_eq!(block.transactions_root, trie::ordered_root(&[first.encode(), second.encode()]));
+        let first_used: U256 = result.results[0].as_ref().unwrap().gas_used;
+        let second_used: U256 = result.results[1].as_ref().unwrap().gas_used;
+        let receipts: Vec<Vec<u8>> = vec![
+            Receipt::new(TxType::Legacy, true, first_used, Vec::new()).encode(),
+            Receipt::new(TxType::Legacy, true, first_used + second_used, logged(logger)).encode(),
+        ];
+        assert_eq!(block.receipts_root, trie::ordered_root(&receipts));
+        assert_eq!(block.withdrawals_root, Some(trie::ordered_root(&withdrawals.iter().map(rlp::encode).collect::<Vec<Vec<u8>>>())));
+        assert_eq!(block.requests_hash, Some(Requests::new().hash()));
+        assert_eq!(block.state_root, chain.state_root());
+        assert_eq!(block.gas_used, first_used + second_used);
+        assert_eq!(block.ommers_hash, EMPTY_OMMERS_HASH);
+
+        // nothing carries over into the next block
+        assert!(chain.get_transactions().is_empty());
+        assert!(chain.get_receipts().is_empty());
+        let empty: Block = chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(empty.transactions_root, EMPTY_ROOT_HASH);
+        assert_eq!(empty.receipts_root, EMPTY_ROOT_HASH);
+        assert_eq!(empty.withdrawals_root, Some(EMPTY_ROOT_HASH));
+    }
+
+    // what "60aa5f5260bb60205fa100" logs: the word 0xaa under the topic 0xbb
+    fn logged(address: U256) -> Vec<Log> {
+        let mut data: Vec<u8> = vec![0; 31];
+        data.push(0xaa);
+        return vec![Log::new(address, vec![U256::new(0xbb)], data)];
+    }
+
+    fn block_with(gas_used: u64, basefee: Option<u64>) -> Block {
+        let mut block: Block = Block::genesis();
+        block.gaslimit = U256::new(30_000_000);
+        block.gas_used = U256::from(gas_used);
+        block.basefee = basefee.map(U256::from);
+        return block;
+    }
+
+    #[test]
+    fn test_next_basefee() {
+        // target 15M, the base fee moves by at most an eighth
+        let cases: [(u64, Option<u64>, u64); 8] = [
+            (15_000_000, Some(1_000_000_000), 1_000_000_000),
+            (30_000_000, Some(1_000_000_000), 1_125_000_000),
+            (0, Some(1_000_000_000), 875_000_000),
+            (22_500_000, Some(1_000_000_000), 1_062_500_000),
+            (7_500_000, Some(1_000_000_000), 937_500_000),
+            // above target always raises it by at least one
+            (15_000_001, Some(100), 101),
+            (0, Some(7), 7),
+            // the parent of the London fork block has no base fee
+            (30_000_000, None, INITIAL_BASE_FEE),
+        ];
+        for (i, (gas_used, basefee, expected)) in cases.iter().enumerate() {
+            assert_eq!(block_with(*gas_used, *basefee).next_basefee(), U256::from(*expected), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn test_london_fork_block() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Berlin);
+        let berlin: Block = chain.advance_blocks(2, SLOT_TIME).unwrap();
+        assert_eq!(berlin.basefee, None);
+        assert_eq!(chain.get_block(U256::new(1)).unwrap().basefee, None);
+
+        chain.set_hardfork(Hardfork::London);
+        assert_eq!(chain.get_current_block().basefee, Some(U256::from(INITIAL_BASE_FEE)));
+        let used: U256 = send(&mut chain, Some(U256::new(0xbeef)), U256::new(1), Vec::new()).gas_used;
+        let fork: Block = chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(fork.basefee, Some(U256::from(INITIAL_BASE_FEE)));
+
+        // the block after the fork block follows the usual rule
+        let target: U256 = fork.gaslimit / 2;
+        let expected: U256 = U256::from(INITIAL_BASE_FEE) - U256::from(INITIAL_BASE_FEE) * (target - used) / target / 8;
+        assert_eq!(chain.get_current_block().basefee, Some(expected));
+    }
+
+    fn blob_hash(i: u8) -> U256 {
+        let mut hash: [u8; 32] = [i; 32];
+        hash[0] = crate::transaction::VERSIONED_HASH_VERSION_KZG;
+        return U256::from_be_bytes(hash);
+    }
+
+    fn blob_transaction(chain: &mut BlockChain, to: U256, blobs: u8) -> Transaction {
+        let mut tx: Transaction = unsigned(chain, Some(to), U256::new(0), Vec::new());
+        tx.tx_type = TxType::Blob;
+        tx.max_fee_per_blob_gas = U256::new(1_000_000_000);
+        tx.blob_versioned_hashes = (1..=blobs).map(blob_hash).collect();
+        return tx;
+    }
+
+    #[test]
+    fn test_next_excess_blob_gas() {
+        let blob: u64 = GAS_PER_BLOB;
+        // excess, used, next excess under Cancun (target 3) and Prague (target 6)
+        let cases: [(u64, u64, u64, u64); 7] = [
+            (0, 0, 0, 0),
+            (0, 3 * blob, 0, 0),
+            (0, 6 * blob, 3 * blob, 0),
+            (3 * blob, 0, 0, 0),
+            (3 * blob, 3 * blob, 3 * blob, 0),
+            (3 * blob, 6 * blob, 6 * blob, 3 * blob),
+            (10 * blob, blob, 8 * blob, 5 * blob),
+        ];
+        for (i, (excess, used, cancun, prague)) in cases.iter().enumerate() {
+            let mut block: Block = Block::genesis();
+            block.excess_blob_gas = Some(U256::from(*excess));
+            block.blob_gas_used = Some(U256::from(*used));
+            assert_eq!(block.next_excess_blob_gas(Hardfork::Cancun), U256::from(*cancun), "case {}", i);
+            assert_eq!(block.next_excess_blob_gas(Hardfork::Prague), U256::from(*prague), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn test_excess_blob_gas_carries_over() {
+        let mut chain: BlockChain = BlockChain::new();
+        let blob: U256 = U256::from(GAS_PER_BLOB);
+        let tx: Transaction = blob_transaction(&mut chain, U256::new(0xbeef), 6);
+        assert!(execute(&mut chain, tx).unwrap().success);
+        let full: Block = chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(full.blob_gas_used, Some(blob * 6));
+
+        // three blobs above target are carried into the next block
+        let child: Block = chain.get_current_block();
+        assert_eq!(child.blob_gas_used, Some(U256::new(0)));
+        assert_eq!(child.excess_blob_gas, Some(blob * 3));
+        assert_eq!(child.blob_basefee(Hardfork::Cancun), utils::fake_exponential(
+            U256::new(1), blob * 3, U256::from(Hardfork::Cancun.blob_base_fee_update_fraction())
+        ));
+
+        // blocks below target drain it again
+        let tx: Transaction = blob_transaction(&mut chain, U256::new(0xbeef), 2);
+        assert!(execute(&mut chain, tx).unwrap().success);
+        chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(chain.get_current_block().excess_blob_gas, Some(blob * 2));
+        chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(chain.get_current_block().excess_blob_gas, Some(U256::new(0)));
+    }
+
+    #[test]
+    fn test_blobhash() {
+        // stores BLOBHASH of indices 0, 1 and 2 in the same slots, then of 2^256-1 in slot 3
+        let contract: U256 = U256::new(0x4242);
+        let mut code: Vec<u8> = (0..3u8).flat_map(|i| [0x60, i, 0x49, 0x60, i, 0x55]).collect();
+        code.push(0x7f);
+        code.extend([0xff; 32]);
+        code.extend([0x49, 0x60, 0x03, 0x55]);
+
+        let mut chain: BlockChain = BlockChain::new();
+        chain.add_contract_bin(contract, code.clone());
+        let tx: Transaction = blob_transaction(&mut chain, contract, 2);
+        assert!(execute(&mut chain, tx).unwrap().success);
+        assert_eq!(chain.get_storage(contract, U256::new(0)), blob_hash(1));
+        assert_eq!(chain.get_storage(contract, U256::new(1)), blob_hash(2));
+        assert_eq!(chain.get_storage(contract, U256::new(2)), U256::new(0));
+        assert_eq!(chain.get_storage(contract, U256::new(3)), U256::new(0));
+
+        // transactions without blobs see none
+        chain.add_contract_bin(contract, vec![0x60, 0x00, 0x49, 0x60, 0x05, 0x55]);
+        assert!(send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+        assert_eq!(chain.get_storage(contract, U256::new(5)), U256::new(0));
+
+        // BLOBHASH is an invalid instruction before Cancun
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Shanghai);
+        chain.add_contract_bin(contract, code);
+        assert!(!send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+    }
+
+    #[test]
+    fn test_withdrawals() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Shanghai);
+        let existing: U256 = U256::new(0xaaaa);
+        let contract: U256 = U256::new(0xcccc);
+        chain.fund(existing, U256::new(1));
+        // withdrawals are not calls, the code never runs
+        chain.add_contract_bin(contract, vec![0xfe]);
+
+        // the transaction may use all the block gas, withdrawals cost none
+        let mut tx: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(0), Vec::new());
+        tx.gas_limit = chain.get_current_block().gaslimit;
+        let withdrawals: Vec<Withdrawal> = vec![
+            Withdrawal::new(0, 1, existing, 5),
+            Withdrawal::new(1, 2, U256::new(0xbbbb), 3),
+            Withdrawal::new(2, 3, existing, 1),
+            Withdrawal::new(3, 4, contract, u64::MAX),
+            Withdrawal::new(4, 5, U256::new(0xdddd), 0),
+        ];
+        let result: BlockResult = chain.build_block(&[signed(tx)], &withdrawals).unwrap();
+        let used: U256 = result.results[0].as_ref().unwrap().gas_used;
+        assert_eq!(result.block.gas_used, used);
+
+        let gwei: U256 = U256::from(GWEI);
+        assert_eq!(chain.get_balance(existing), U256::new(1) + gwei * 6);
+        assert_eq!(chain.get_balance(U256::new(0xbbbb)), gwei * 3);
+        assert_eq!(chain.get_balance(contract), U256::from(u64::MAX) * gwei);
+        assert!(!chain.has_account(U256::new(0xdddd)));
+
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Paris);
+        assert_eq!(chain.build_block(&[], &withdrawals).err(), Some(BlockError::WithdrawalsNotSupported));
+        assert_eq!(chain.get_balance(existing), U256::new(0));
+    }
+
+    const ETHER: u64 = 1_000_000_000_000_000_000;
+
+    // a stale sibling of block `number + 1`, mined by `coinbase`
+    fn ommer(chain: &BlockChain, number: u64, coinbase: u64) -> Block {
+        let mut ommer: Block = chain.get_block(U256::from(number)).unwrap().child(SLOT_TIME);
+        ommer.coinbase = U256::from(coinbase);
+        return ommer;
+    }
+
+    #[test]
+    fn test_block_rewards() {
+        let cases: [(Hardfork, u64); 5] = [
+            (Hardfork::Frontier, 5 * ETHER),
+            (Hardfork::Homestead, 5 * ETHER),
+            (Hardfork::Byzantium, 3 * ETHER),
+            (Hardfork::Constantinople, 2 * ETHER),
+            (Hardfork::Paris, 0),
+        ];
+        for (hardfork, reward) in cases {
+            let mut chain: BlockChain = BlockChain::new();
+            chain.set_hardfork(hardfork);
+            chain.set_coinbase(U256::new(0xc0ffee));
+            chain.seal_block(SLOT_TIME).unwrap();
+            assert_eq!(chain.get_balance(U256::new(0xc0ffee)), U256::from(reward), "{:?}", hardfork);
+        }
+    }
+
+    #[test]
+    fn test_ommer_rewards() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Constantinople);
+        chain.advance_blocks(8, SLOT_TIME).unwrap();
+        chain.set_coinbase(U256::new(0xc0ffee));
+        // siblings of blocks 7 and 2, one and six blocks deep
+        chain.add_ommer(ommer(&chain, 6, 0xaaaa)).unwrap();
+        chain.add_ommer(ommer(&chain, 1, 0xbbbb)).unwrap();
+        let block: Block = chain.seal_block(SLOT_TIME).unwrap();
+
+        let reward: U256 = U256::from(2 * ETHER);
+        assert_eq!(chain.get_balance(U256::new(0xc0ffee)), reward + reward / 32 * 2);
+        assert_eq!(chain.get_balance(U256::new(0xaaaa)), reward * 7 / 8);
+        assert_eq!(chain.get_balance(U256::new(0xbbbb)), reward * 2 / 8);
+        let ommers: Vec<Block> = vec![ommer(&chain, 6, 0xaaaa), ommer(&chain, 1, 0xbbbb)];
+        assert_eq!(block.ommers_hash, utils::keccak256(&rlp::encode(&ommers)));
+    }
+
+    #[test]
+    fn test_ommer_validation() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Constantinople);
+        chain.advance_blocks(8, SLOT_TIME).unwrap();
+
+        // seven blocks deep
+        assert_eq!(chain.add_ommer(ommer(&chain, 0, 0xaaaa)), Err(BlockError::InvalidOmmer { number: U256::new(1) }));
+        // as recent as the block itself
+        assert_eq!(chain.add_ommer(ommer(&chain, 7, 0xaaaa)), Err(BlockError::InvalidOmmer { number: U256::new(8) }));
+        // already on the chain
+        let canonical: Block = chain.get_block(U256::new(5)).unwrap();
+        assert_eq!(chain.add_ommer(canonical), Err(BlockError::InvalidOmmer { number: U256::new(5) }));
+        // no known parent
+        let mut orphan: Block = ommer(&chain, 5, 0xaaaa);
+        orphan.parent_hash = U256::new(0x1234);
+        assert_eq!(chain.add_ommer(orphan), Err(BlockError::InvalidOmmer { number: U256::new(6) }));
+
+        chain.add_ommer(ommer(&chain, 5, 0xaaaa)).unwrap();
+        let duplicate: U256 = ommer(&chain, 5, 0xaaaa).hash();
+        assert_eq!(chain.add_ommer(ommer(&chain, 5, 0xaaaa)), Err(BlockError::DuplicateOmmer { hash: duplicate }));
+        chain.add_ommer(ommer(&chain, 5, 0xbbbb)).unwrap();
+        assert_eq!(chain.add_ommer(ommer(&chain, 4, 0xcccc)), Err(BlockError::TooManyOmmers));
+
+        // an ommer is only rewarded once
+        chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(chain.add_ommer(ommer(&chain, 5, 0xaaaa)), Err(BlockError::DuplicateOmmer { hash: duplicate }));
+        chain.add_ommer(ommer(&chain, 5, 0xcccc)).unwrap();
+
+        // and there are none after the merge
+        chain.set_hardfork(Hardfork::Paris);
+        assert_eq!(chain.add_ommer(ommer(&chain, 6, 0xdddd)), Err(BlockError::OmmersNotSupported));
+    }
+
+    #[test]
+    fn test_system_calls() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        chain.deploy_system_contracts();
+        let genesis: Block = chain.seal_block(SLOT_TIME).unwrap();
+        chain.set_parent_beacon_block_root(U256::new(0x1234));
+        let timestamp: U256 = chain.get_current_block().timestamp;
+
+        let tx: Transaction = unsigned(&mut chain, Some(U256::new(0xbeef)), U256::new(0), Vec::new());
+        let balance: U256 = chain.get_balance(tx.from);
+        let result: ExecutionResult = execute(&mut chain, tx.clone()).unwrap();
+        // EIP-4788 ring buffer: the timestamp, and the root 8191 slots further
+        let slot: U256 = timestamp % 8191;
+        assert_eq!(chain.get_storage(BEACON_ROOTS_ADDRESS, slot), timestamp);
+        assert_eq!(chain.get_storage(BEACON_ROOTS_ADDRESS, slot + 8191), U256::new(0x1234));
+        // EIP-2935: the parent hash under the parent's number
+        assert_eq!(chain.get_storage(HISTORY_STORAGE_ADDRESS, U256::new(0)), genesis.hash());
+
+        // the transaction pays for itself only, the system calls are free
+        assert_eq!(chain.get_balance(tx.from), balance - result.gas_used * U256::new(2_000_000_000));
+        assert_eq!(chain.get_nonce(tx.from), U256::new(1));
+        assert!(!chain.has_account(SYSTEM_ADDRESS));
+        let block: Block = chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(block.gas_used, result.gas_used);
+
+        // blocks without transactions run them too
+        let timestamp: U256 = chain.get_current_block().timestamp;
+        chain.set_parent_beacon_block_root(U256::new(0x5678));
+        chain.seal_block(SLOT_TIME).unwrap();
+        assert_eq!(chain.get_storage(BEACON_ROOTS_ADDRESS, timestamp % 8191 + 8191), U256::new(0x5678));
+        assert_eq!(chain.get_storage(HISTORY_STORAGE_ADDRESS, U256::new(1)), block.hash());
+        assert_eq!(chain.get_nonce(SYSTEM_ADDRESS), U256::new(0));
+    }
+
+    #[test]
+    fn test_system_calls_by_fork() {
+        // 4788 from Cancun, 2935 from Prague
+        let cases: [(Hardfork, bool, bool); 3] = [
+            (Hardfork::Shanghai, false, false),
+            (Hardfork::Cancun, true, false),
+            (Hardfork::Prague, true, true),
+        ];
+        for (hardfork, beacon_root, parent_hash) in cases {
+            let mut chain: BlockChain = BlockChain::new();
+            chain.set_hardfork(hardfork);
+            chain.deploy_system_contracts();
+            let genesis: Block = chain.seal_block(SLOT_TIME).unwrap();
+            chain.set_parent_beacon_block_root(U256::new(0x1234));
+            let slot: U256 = chain.get_current_block().timestamp % 8191 + 8191;
+            chain.seal_block(SLOT_TIME).unwrap();
+            assert_eq!(chain.get_storage(BEACON_ROOTS_ADDRESS, slot) == 0x1234, beacon_root, "{:?}", hardfork);
+            assert_eq!(chain.get_storage(HISTORY_STORAGE_ADDRESS, U256::new(0)) == genesis.hash(), parent_hash, "{:?}", hardfork);
+        }
+    }
+
+    // second development key, the authority of the EIP-7702 tests
+    const AUTHORITY_KEY: &str = "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d";
+
+    fn authority() -> U256 {
+        return crate::signature::secret_key_to_address(U256::from_str_hex(AUTHORITY_KEY).unwrap()).unwrap();
+    }
+
+    fn authorization(chain_id: U256, address: U256, nonce: u64) -> Authorization {
+        let mut authorization: Authorization = Authorization {
+            chain_id: chain_id,
+            address: address,
+            nonce: U256::from(nonce),
+            y_parity: U256::new(0),
+            r: U256::new(0),
+            s: U256::new(0)
         };
-        self.logs.push(log);
+        authorization.sign(U256::from_str_hex(AUTHORITY_KEY).unwrap()).unwrap();
+        return authorization;
+    }
+
+    fn set_code(chain: &mut BlockChain, authorizations: Vec<Authorization>, data: Vec<u8>) -> ExecutionResult {
+        let mut tx: Transaction = unsigned(chain, Some(U256::new(0xbeef)), U256::new(0), data);
+        tx.tx_type = TxType::SetCode;
+        tx.authorization_list = authorizations;
+        return execute(chain, tx).unwrap();
     }
 
-    pub fn add_log4(&mut self, data: Vec<u8>, topic1: U256, topic2: U256, topic3: U256, topic4: U256) {
-        let log: Log = Log{
-            data: data,
-            topic1: topic1,
-            topic2: topic2,
-            topic3: topic3,
-            topic4: topic4
+    #[test]
+    fn test_authorizations() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        let chain_id: U256 = chain.get_current_block().chainid;
+        let delegate: U256 = U256::new(0x4242);
+        // stores 1 in slot 0 of whoever runs it
+        chain.add_contract_bin(delegate, vec![0x60, 0x01, 0x5f, 0x55, 0x00]);
+
+        set_code(&mut chain, vec![authorization(chain_id, delegate, 0)], Vec::new());
+        assert_eq!(chain.get_code(authority()), delegation_designator(delegate));
+        assert_eq!(chain.get_nonce(authority()), U256::new(1));
+
+        // chain id 0 is valid on every chain, another chain id is skipped
+        set_code(&mut chain, vec![authorization(U256::new(0), U256::new(0x5555), 1)], Vec::new());
+        assert_eq!(chain.get_code(authority()), delegation_designator(U256::new(0x5555)));
+        set_code(&mut chain, vec![authorization(chain_id + 1, delegate, 2)], Vec::new());
+        assert_eq!(chain.get_code(authority()), delegation_designator(U256::new(0x5555)));
+        assert_eq!(chain.get_nonce(authority()), U256::new(2));
+        // so is a stale nonce, and a later valid entry still applies
+        set_code(&mut chain, vec![authorization(chain_id, U256::new(0x6666), 1), authorization(chain_id, delegate, 2)], Vec::new());
+        assert_eq!(chain.get_code(authority()), delegation_designator(delegate));
+        assert_eq!(chain.get_nonce(authority()), U256::new(3));
+
+        // calls run the delegate's code on the authority's storage
+        assert!(send(&mut chain, Some(authority()), U256::new(0), Vec::new()).success);
+        assert_eq!(chain.get_storage(authority(), U256::new(0)), U256::new(1));
+        assert_eq!(chain.get_storage(delegate, U256::new(0)), U256::new(0));
+
+        // the zero address clears the delegation, the nonce still moves
+        set_code(&mut chain, vec![authorization(chain_id, U256::new(0), 3)], Vec::new());
+        assert!(chain.get_code(authority()).is_empty());
+        assert_eq!(chain.get_nonce(authority()), U256::new(4));
+    }
+
+    #[test]
+    fn test_delegation_designator_is_visible() {
+        // EXTCODESIZE, EXTCODEHASH and EXTCODECOPY see the designator, not
+        // the delegate's code, into slots 0, 1 and 2
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        let chain_id: U256 = chain.get_current_block().chainid;
+        let delegate: U256 = U256::new(0x4242);
+        chain.add_contract_bin(delegate, vec![0x60, 0x01, 0x5f, 0x55, 0x00]);
+        set_code(&mut chain, vec![authorization(chain_id, delegate, 0)], Vec::new());
+
+        let address: Vec<u8> = authority().to_be_bytes()[12..].to_vec();
+        let mut code: Vec<u8> = vec![0x73];
+        code.extend(&address);
+        code.extend([0x3b, 0x5f, 0x55, 0x73]);
+        code.extend(&address);
+        code.extend([0x3f, 0x60, 0x01, 0x55, 0x60, 0x20, 0x5f, 0x5f, 0x73]);
+        code.extend(&address);
+        code.extend([0x3c, 0x5f, 0x51, 0x60, 0x02, 0x55, 0x00]);
+        let inspector: U256 = U256::new(0x5151);
+        chain.add_contract_bin(inspector, code);
+        assert!(send(&mut chain, Some(inspector), U256::new(0), Vec::new()).success);
+
+        let designator: Vec<u8> = delegation_designator(delegate);
+        let mut word: [u8; 32] = [0; 32];
+        word[..23].copy_from_slice(&designator);
+        assert_eq!(chain.get_storage(inspector, U256::new(0)), U256::new(23));
+        assert_eq!(chain.get_storage(inspector, U256::new(1)), utils::keccak256(&designator));
+        assert_eq!(chain.get_storage(inspector, U256::new(2)), U256::from_be_bytes(word));
+    }
+
+    #[test]
+    fn test_authorization_refund() {
+        // an authority that already exists refunds PER_EMPTY_ACCOUNT_COST - PER_AUTH_BASE_COST.
+        // The calldata keeps the refund under its cap of a fifth of the gas used
+        let data: Vec<u8> = vec![0xff; 2000];
+        let mut used: Vec<U256> = Vec::new();
+        for exists in [false, true] {
+            let mut chain: BlockChain = BlockChain::new();
+            chain.set_hardfork(Hardfork::Prague);
+            if exists {
+                chain.fund(authority(), U256::new(1));
+            }
+            let chain_id: U256 = chain.get_current_block().chainid;
+            let result: ExecutionResult = set_code(&mut chain, vec![authorization(chain_id, U256::new(0x4242), 0)], data.clone());
+            assert_eq!(chain.get_nonce(authority()), U256::new(1));
+            used.push(result.gas_used);
+        }
+        assert!(used[0] / 5 > U256::from(PER_EMPTY_ACCOUNT_COST - PER_AUTH_BASE_COST));
+        assert_eq!(used[0] - used[1], U256::from(PER_EMPTY_ACCOUNT_COST - PER_AUTH_BASE_COST));
+    }
+
+    #[test]
+    fn test_account_api() {
+        let mut chain: BlockChain = BlockChain::new();
+        let address: U256 = U256::new(0xf00d);
+
+        // reads see an empty account and do not create one
+        assert_eq!(chain.get_balance(address), U256::new(0));
+        assert_eq!(chain.get_nonce(address), U256::new(0));
+        assert!(chain.get_code(address).is_empty());
+        assert_eq!(chain.get_storage(address, U256::new(1)), U256::new(0));
+        assert!(!chain.has_account(address));
+        assert!(chain.is_empty_account(address));
+
+        chain.create_account(address);
+        assert!(chain.has_account(address));
+        assert!(chain.is_empty_account(address));
+
+        chain.fund(address, U256::new(5));
+        chain.fund(address, U256::new(7));
+        assert_eq!(chain.get_balance(address), U256::new(12));
+        assert!(!chain.is_empty_account(address));
+        chain.set_balance(address, U256::new(0));
+        assert!(chain.is_empty_account(address));
+        // creating an account that exists keeps it as it is
+        chain.set_nonce(address, U256::new(3));
+        chain.create_account(address);
+        assert_eq!(chain.get_nonce(address), U256::new(3));
+        assert!(!chain.is_empty_account(address));
+        chain.set_nonce(address, U256::new(0));
+
+        // storage alone does not make an account non-empty, code does
+        chain.set_storage(address, U256::new(1), U256::new(0xaa));
+        assert_eq!(chain.get_storage(address, U256::new(1)), U256::new(0xaa));
+        assert!(chain.is_empty_account(address));
+        chain.set_code(address, vec![0x00]);
+        assert_eq!(chain.get_code(address), vec![0x00]);
+        assert!(!chain.is_empty_account(address));
+
+        // setters create the accounts they write to
+        chain.set_nonce(U256::new(0xbeef), U256::new(1));
+        assert!(chain.has_account(U256::new(0xbeef)));
+    }
+
+    #[test]
+    fn test_missing_accounts_from_evm() {
+        // BALANCE, EXTCODESIZE, EXTCODEHASH and the success of a CALL with no
+        // value to `target`, into slots 0 to 3
+        let inspect = |target: u16| -> Vec<u8> {
+            let [high, low]: [u8; 2] = target.to_be_bytes();
+            return vec![
+                0x61, high, low, 0x31, 0x5f, 0x55,
+                0x61, high, low, 0x3b, 0x60, 0x01, 0x55,
+                0x61, high, low, 0x3f, 0x60, 0x02, 0x55,
+                0x5f, 0x5f, 0x5f, 0x5f, 0x5f, 0x61, high, low, 0x61, 0xff, 0xff, 0xf1, 0x60, 0x03, 0x55, 0x00,
+            ];
         };
-        self.logs.push(log);
+        let contract: U256 = U256::new(0x4242);
+        let cases: [(u16, U256, U256); 2] = [
+            // missing: empty, no code hash
+            (0xdead, U256::new(0), U256::new(0)),
+            // a funded account without code: its hash is the empty code's
+            (0xf00d, U256::new(1), utils::keccak256(&[])),
+        ];
+        for (target, balance, hash) in cases {
+            let mut chain: BlockChain = BlockChain::new();
+            chain.fund(U256::new(0xf00d), U256::new(1));
+            chain.add_contract_bin(contract, inspect(target));
+            for slot in 0..4 {
+                chain.set_storage(contract, U256::new(slot), U256::new(0xff));
+            }
+            assert!(send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+            assert_eq!(chain.get_storage(contract, U256::new(0)), balance);
+            assert_eq!(chain.get_storage(contract, U256::new(1)), U256::new(0));
+            assert_eq!(chain.get_storage(contract, U256::new(2)), hash);
+            assert_eq!(chain.get_storage(contract, U256::new(3)), U256::new(1));
+        }
+        // the empty account touched by the call is not left behind (EIP-161)
+        let mut chain: BlockChain = BlockChain::new();
+        chain.add_contract_bin(contract, inspect(0xdead));
+        send(&mut chain, Some(contract), U256::new(0), Vec::new());
+        assert!(!chain.has_account(U256::new(0xdead)));
+    }
+
+    // a contract that selfdestructs to 0xbeef
+    fn destructible(chain: &mut BlockChain, hardfork: Hardfork) -> U256 {
+        let contract: U256 = U256::new(0x4242);
+        chain.set_hardfork(hardfork);
+        chain.add_contract_bin(contract, hex::decode("61beefff").unwrap());
+        chain.set_balance(contract, U256::new(500));
+        return contract;
+    }
+
+    #[test]
+    fn test_selfdestruct_before_cancun() {
+        let mut chain: BlockChain = BlockChain::new();
+        let contract: U256 = destructible(&mut chain, Hardfork::Shanghai);
+        assert!(send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+        assert!(!chain.has_account(contract));
+        // the beneficiary is created and credited
+        assert_eq!(chain.get_balance(U256::new(0xbeef)), U256::new(500));
+    }
+
+    #[test]
+    fn test_selfdestruct_since_cancun() {
+        // EIP-6780: only the balance moves out of a contract that already existed
+        let mut chain: BlockChain = BlockChain::new();
+        let contract: U256 = destructible(&mut chain, Hardfork::Cancun);
+        chain.set_balance(U256::new(0xbeef), U256::new(1));
+        assert!(send(&mut chain, Some(contract), U256::new(0), Vec::new()).success);
+        assert_eq!(chain.get_code(contract), hex::decode("61beefff").unwrap());
+        assert_eq!(chain.get_balance(contract), U256::new(0));
+        assert_eq!(chain.get_balance(U256::new(0xbeef)), U256::new(501));
+
+        // a contract created in the same transaction is still deleted
+        let result: ExecutionResult = send(&mut chain, None, U256::new(100), hex::decode("61beefff").unwrap());
+        assert!(result.success);
+        assert!(!chain.has_account(result.contract_address.unwrap()));
+        assert_eq!(chain.get_balance(U256::new(0xbeef)), U256::new(601));
+    }
+
+    #[test]
+    fn test_selfdestruct_refund() {
+        // refunded until London (EIP-3529), capped at half the gas used
+        let mut london: BlockChain = BlockChain::new();
+        let contract: U256 = destructible(&mut london, Hardfork::London);
+        let without_refund: U256 = send(&mut london, Some(contract), U256::new(0), Vec::new()).gas_used;
+
+        let mut berlin: BlockChain = BlockChain::new();
+        let contract: U256 = destructible(&mut berlin, Hardfork::Berlin);
+        let with_refund: U256 = send(&mut berlin, Some(contract), U256::new(0), Vec::new()).gas_used;
+        assert!(without_refund / 2 > 24000);
+        assert_eq!(with_refund, without_refund - 24000);
+    }
+
+    // stand-in for a request contract: returns `data` when called the way
+    // the protocol calls it, from SYSTEM_ADDRESS with empty calldata, and
+    // reverts otherwise
+    fn request_contract(data: &[u8]) -> Vec<u8> {
+        let size: [u8; 2] = (data.len() as u16).to_be_bytes();
+        let mut code: Vec<u8> = vec![0x36, 0x60, 0x1e, 0x57, 0x33, 0x73];
+        code.extend(SYSTEM_ADDRESS.to_be_bytes()[12..].to_vec());
+        code.extend([0x14, 0x60, 0x22, 0x57, 0x5b, 0x5f, 0x5f, 0xfd, 0x5b]);
+        code.extend([0x61, size[0], size[1], 0x60, 0x2f, 0x5f, 0x39, 0x61, size[0], size[1], 0x5f, 0xf3]);
+        code.extend(data);
+        return code;
+    }
+
+    #[test]
+    fn test_requests_from_request_contracts() {
+        let withdrawal = WithdrawalRequest {
+            source_address: U256::new(0xaa),
+            validator_pubkey: vec![0x11; 48],
+            amount: 32_000_000_000
+        };
+        let consolidation = ConsolidationRequest {
+            source_address: U256::new(0xbb),
+            source_pubkey: vec![0x22; 48],
+            target_pubkey: vec![0x33; 48]
+        };
+
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        chain.deploy_request_contracts(request_contract(&withdrawal.encode()), request_contract(&consolidation.encode()));
+        let block: Block = chain.advance_blocks(2, SLOT_TIME).unwrap();
+        let expected: Requests = Requests {
+            deposits: Vec::new(),
+            withdrawals: vec![withdrawal],
+            consolidations: vec![consolidation]
+        };
+        assert_eq!(chain.get_requests(), &expected);
+        assert_eq!(chain.get_block(block.number - 1).unwrap().requests_hash, Some(expected.hash()));
+    }
+
+    #[test]
+    fn test_request_contract_calling_convention() {
+        // the stand-ins refuse anyone but the protocol
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        chain.deploy_request_contracts(request_contract(&[0xaa; 76]), request_contract(&[0xbb; 116]));
+        let result: ExecutionResult = send(&mut chain, Some(requests::WITHDRAWAL_REQUEST_ADDRESS), U256::new(0), Vec::new());
+        assert!(!result.success);
+        let result: evm::FrameResult = chain.system_call(requests::CONSOLIDATION_REQUEST_ADDRESS, vec![0x01]).unwrap();
+        assert!(!result.success);
+        let result: evm::FrameResult = chain.system_call(requests::CONSOLIDATION_REQUEST_ADDRESS, Vec::new()).unwrap();
+        assert_eq!(result.output, vec![0xbb; 116]);
+    }
+
+    #[test]
+    fn test_failed_request_contract() {
+        // the withdrawal contract writes to its storage, the consolidation contract fails
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        chain.deploy_request_contracts(vec![0x60, 0x01, 0x5f, 0x55, 0x00], vec![0xfe]);
+        let number: U256 = chain.get_current_block().number;
+        let expected: BlockError = BlockError::SystemCallFailed { address: requests::CONSOLIDATION_REQUEST_ADDRESS };
+        assert_eq!(chain.seal_block(SLOT_TIME).err(), Some(expected.clone()));
+        assert_eq!(chain.build_block(&[], &[]).err(), Some(expected));
+
+        // the block stays open and the withdrawal contract's write is undone
+        assert_eq!(chain.get_current_block().number, number);
+        assert_eq!(chain.get_storage(requests::WITHDRAWAL_REQUEST_ADDRESS, U256::new(0)), U256::new(0));
+
+        chain.deploy_request_contracts(vec![0x5f, 0x5f, 0xfd], returning(&[]));
+        let expected: BlockError = BlockError::SystemCallFailed { address: requests::WITHDRAWAL_REQUEST_ADDRESS };
+        assert_eq!(chain.advance_block().err(), Some(expected));
+
+        chain.deploy_request_contracts(returning(&[]), returning(&[]));
+        assert_eq!(chain.seal_block(SLOT_TIME).unwrap().number, number);
+    }
+
+    #[test]
+    fn test_no_requests_without_request_contracts() {
+        let mut chain: BlockChain = BlockChain::new();
+        chain.set_hardfork(Hardfork::Prague);
+        chain.deploy_system_contracts();
+        chain.advance_blocks(2, SLOT_TIME).unwrap();
+        assert_eq!(chain.get_requests(), &Requests::new());
     }
 }
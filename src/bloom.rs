@@ -0,0 +1,74 @@
+#![allow(clippy::needless_return)]
+
+use ethnum::{U256};
+
+use crate::blockchain::Log;
+use crate::utils;
+
+
+// 2048 bit filter over log addresses and topics (yellow paper, section 4.3.1)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Bloom(pub [u8; 256]);
+
+impl Bloom {
+    pub fn new() -> Bloom {
+        return Bloom([0; 256]);
+    }
+
+    pub fn from_logs(logs: &[Log]) -> Bloom {
+        let mut bloom: Bloom = Bloom::new();
+        for log in logs {
+            bloom.accrue_log(log);
+        }
+        return bloom;
+    }
+
+    // the three bits an input sets, taken from the first six bytes of its hash
+    fn bits(input: &[u8]) -> [(usize, u8); 3] {
+        let hash: [u8; 32] = utils::keccak256(input).to_be_bytes();
+        let mut bits: [(usize, u8); 3] = [(0, 0); 3];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            let index: usize = (((hash[2 * i] as usize) << 8) | hash[2 * i + 1] as usize) & 2047;
+            *bit = (255 - index / 8, 1 << (index % 8));
+        }
+        return bits;
+    }
+
+    pub fn accrue(&mut self, input: &[u8]) {
+        for (byte, mask) in Bloom::bits(input) {
+            self.0[byte] |= mask;
+        }
+    }
+
+    pub fn accrue_log(&mut self, log: &Log) {
+        self.accrue(&log.address.to_be_bytes()[12..32]);
+        for topic in &log.topics {
+            self.accrue(&topic.to_be_bytes());
+        }
+    }
+
+    pub fn accrue_bloom(&mut self, other: &Bloom) {
+        for i in 0..256 {
+            self.0[i] |= other.0[i];
+        }
+    }
+
+    // false positives are possible, false negatives are not
+    pub fn contains_input(&self, input: &[u8]) -> bool {
+        return Bloom::bits(input).iter().all(|&(byte, mask)| self.0[byte] & mask != 0);
+    }
+
+    pub fn contains_address(&self, address: U256) -> bool {
+        return self.contains_input(&address.to_be_bytes()[12..32]);
+    }
+
+    pub fn contains_topic(&self, topic: U256) -> bool {
+        return self.contains_input(&topic.to_be_bytes());
+    }
+}
+
+impl Default for Bloom {
+    fn default() -> Bloom {
+        return Bloom::new();
+    }
+}
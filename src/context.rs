@@ -1,16 +1,20 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 use ethnum::{U256};
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct TX {
     pub origin: U256,
     pub gasprice: U256,
+    pub blob_hashes: Vec<U256>,
 }
 
 impl TX {
     pub fn new(origin: U256, gasprice: U256) -> TX{
         return TX {
             origin: origin,
-            gasprice: gasprice
+            gasprice: gasprice,
+            blob_hashes: Vec::new()
         };
     }
 }
@@ -0,0 +1,736 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::fmt;
+
+
+// EVM Object Format v1 (EIP-3540 and the EIPs bundled with it in EIP-7692).
+// A container separates code from data, splits the code into functions with
+// a declared stack signature and is validated once, when it is deployed, so
+// the interpreter never has to check jumps or stack bounds at run time
+
+pub const MAGIC: [u8; 2] = [0xEF, 0x00];
+pub const VERSION: u8 = 0x01;
+
+const KIND_TYPES: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_CONTAINER: u8 = 0x03;
+const KIND_DATA: u8 = 0x04;
+const TERMINATOR: u8 = 0x00;
+
+const MAX_CODE_SECTIONS: usize = 1024;
+const MAX_CONTAINER_SECTIONS: usize = 256;
+const MAX_STACK_INCREASE: u16 = 0x03FF;
+const MAX_IO: u8 = 0x7F;
+pub const MAX_STACK_HEIGHT: usize = 1024;
+pub const NON_RETURNING: u8 = 0x80;
+
+pub const DATALOAD: u8 = 0xD0;
+pub const DATALOADN: u8 = 0xD1;
+pub const DATASIZE: u8 = 0xD2;
+pub const DATACOPY: u8 = 0xD3;
+pub const RJUMP: u8 = 0xE0;
+pub const RJUMPI: u8 = 0xE1;
+pub const RJUMPV: u8 = 0xE2;
+pub const CALLF: u8 = 0xE3;
+pub const RETF: u8 = 0xE4;
+pub const JUMPF: u8 = 0xE5;
+pub const DUPN: u8 = 0xE6;
+pub const SWAPN: u8 = 0xE7;
+pub const EXCHANGE: u8 = 0xE8;
+pub const EOFCREATE: u8 = 0xEC;
+pub const RETURNCONTRACT: u8 = 0xEE;
+pub const EXTCALL: u8 = 0xF8;
+
+const STOP: u8 = 0x00;
+const RETURN: u8 = 0xF3;
+const REVERT: u8 = 0xFD;
+const INVALID: u8 = 0xFE;
+
+
+pub fn is_eof(code: &[u8]) -> bool {
+    return code.starts_with(&MAGIC);
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EofError {
+    InvalidMagic,
+    InvalidVersion(u8),
+    UnexpectedEnd,
+    MissingSection(u8),
+    ZeroSectionSize,
+    TooManyCodeSections(usize),
+    TooManyContainers(usize),
+    InvalidTypesSize(usize),
+    TrailingBytes,
+    // the top level data section is shorter than its header says
+    TruncatedData,
+    InvalidSectionZeroType,
+    InvalidType(usize),
+    UndefinedInstruction { section: usize, offset: usize, opcode: u8 },
+    TruncatedImmediate { section: usize, offset: usize },
+    InvalidJumpDestination { section: usize, offset: usize },
+    InvalidCodeSection(usize),
+    InvalidDataOffset { section: usize, offset: usize },
+    InvalidContainerIndex(usize),
+    CallToNonReturning { section: usize, offset: usize },
+    // a section's outputs disagree with whether it can return
+    ReturningMismatch(usize),
+    StackUnderflow { section: usize, offset: usize },
+    StackOverflow(usize),
+    InvalidStackHeight { section: usize, offset: usize },
+    InvalidMaxStackIncrease(usize),
+    UnreachableCode { section: usize, offset: usize },
+    NoTerminatingInstruction(usize),
+    UnreachableCodeSection(usize),
+    UnreferencedContainer(usize),
+    IncompatibleContainerKind
+}
+
+impl fmt::Display for EofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EofError::InvalidMagic => write!(f, "missing EOF magic"),
+            EofError::InvalidVersion(version) => write!(f, "unsupported EOF version {}", version),
+            EofError::UnexpectedEnd => write!(f, "container ends unexpectedly"),
+            EofError::MissingSection(kind) => write!(f, "missing section header of kind {}", kind),
+            EofError::ZeroSectionSize => write!(f, "empty section"),
+            EofError::TooManyCodeSections(count) => write!(f, "too many code sections: {}", count),
+            EofError::TooManyContainers(count) => write!(f, "too many container sections: {}", count),
+            EofError::InvalidTypesSize(size) => write!(f, "invalid types section size {}", size),
+            EofError::TrailingBytes => write!(f, "bytes after the data section"),
+            EofError::TruncatedData => write!(f, "data section shorter than declared"),
+            EofError::InvalidSectionZeroType => write!(f, "first code section must take no inputs and not return"),
+            EofError::InvalidType(section) => write!(f, "invalid type of code section {}", section),
+            EofError::UndefinedInstruction { section, offset, opcode } =>
+                write!(f, "undefined instruction {:#04x} at {}:{}", opcode, section, offset),
+            EofError::TruncatedImmediate { section, offset } => write!(f, "truncated immediate at {}:{}", section, offset),
+            EofError::InvalidJumpDestination { section, offset } => write!(f, "invalid jump destination at {}:{}", section, offset),
+            EofError::InvalidCodeSection(index) => write!(f, "reference to missing code section {}", index),
+            EofError::InvalidDataOffset { section, offset } => write!(f, "data offset out of bounds at {}:{}", section, offset),
+            EofError::InvalidContainerIndex(index) => write!(f, "reference to missing container {}", index),
+            EofError::CallToNonReturning { section, offset } => write!(f, "CALLF to a non-returning section at {}:{}", section, offset),
+            EofError::ReturningMismatch(section) => write!(f, "code section {} returns against its type", section),
+            EofError::StackUnderflow { section, offset } => write!(f, "stack underflow at {}:{}", section, offset),
+            EofError::StackOverflow(section) => write!(f, "stack overflow in code section {}", section),
+            EofError::InvalidStackHeight { section, offset } => write!(f, "inconsistent stack height at {}:{}", section, offset),
+            EofError::InvalidMaxStackIncrease(section) => write!(f, "wrong max stack increase for code section {}", section),
+            EofError::UnreachableCode { section, offset } => write!(f, "unreachable code at {}:{}", section, offset),
+            EofError::NoTerminatingInstruction(section) => write!(f, "code section {} falls off its end", section),
+            EofError::UnreachableCodeSection(section) => write!(f, "code section {} is never called", section),
+            EofError::UnreferencedContainer(index) => write!(f, "container {} is never used", index),
+            EofError::IncompatibleContainerKind => write!(f, "container used both as init code and as runtime code")
+        }
+    }
+}
+
+impl std::error::Error for EofError {}
+
+
+// whether a container runs as init code, and so ends in RETURNCONTRACT, or is deployed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContainerKind {
+    Initcode,
+    Runtime
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionType {
+    pub inputs: u8,
+    pub outputs: u8,
+    pub max_stack_increase: u16
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Container {
+    pub types: Vec<FunctionType>,
+    pub code_sections: Vec<Vec<u8>>,
+    pub container_sections: Vec<Vec<u8>>,
+    pub data: Vec<u8>,
+    // declared in the header, the data itself can be shorter until
+    // RETURNCONTRACT appends the aux data
+    pub data_size: usize
+}
+
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl Reader<'_> {
+    fn take(&mut self, size: usize) -> Result<&[u8], EofError> {
+        let end: usize = self.position + size;
+        let slice: &[u8] = self.bytes.get(self.position..end).ok_or(EofError::UnexpectedEnd)?;
+        self.position = end;
+        return Ok(slice);
+    }
+
+    fn u8(&mut self) -> Result<u8, EofError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> Result<usize, EofError> {
+        let bytes: &[u8] = self.take(2)?;
+        return Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize);
+    }
+
+    fn u32(&mut self) -> Result<usize, EofError> {
+        let bytes: &[u8] = self.take(4)?;
+        return Ok(u32::from_be_bytes(bytes.try_into().unwrap()) as usize);
+    }
+
+    fn kind(&mut self, kind: u8) -> Result<(), EofError> {
+        if self.u8()? != kind {
+            return Err(EofError::MissingSection(kind));
+        }
+        return Ok(());
+    }
+
+    // section count followed by one size per section, none of them zero
+    fn sizes(&mut self, wide: bool) -> Result<Vec<usize>, EofError> {
+        let count: usize = self.u16()?;
+        if count == 0 {
+            return Err(EofError::ZeroSectionSize);
+        }
+        let mut sizes: Vec<usize> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let size: usize = if wide { self.u32()? } else { self.u16()? };
+            if size == 0 {
+                return Err(EofError::ZeroSectionSize);
+            }
+            sizes.push(size);
+        }
+        return Ok(sizes);
+    }
+}
+
+
+fn read_u16(code: &[u8], position: usize) -> usize {
+    return u16::from_be_bytes([code[position], code[position + 1]]) as usize;
+}
+
+fn read_i16(code: &[u8], position: usize) -> isize {
+    return i16::from_be_bytes([code[position], code[position + 1]]) as isize;
+}
+
+// bytes following the opcode at `position`
+pub fn immediate_size(code: &[u8], position: usize) -> usize {
+    match code[position] {
+        opcode @ 0x60..=0x7F => (opcode - 0x5F) as usize,
+        RJUMP | RJUMPI | CALLF | JUMPF | DATALOADN => 2,
+        RJUMPV => match code.get(position + 1) {
+            Some(max_index) => 1 + (*max_index as usize + 1) * 2,
+            None => 1
+        },
+        DUPN | SWAPN | EXCHANGE | EOFCREATE | RETURNCONTRACT => 1,
+        _ => 0
+    }
+}
+
+// the operands of EXCHANGE: swaps the n + 1th and the n + m + 1th stack items
+pub fn exchange_operands(immediate: u8) -> (usize, usize) {
+    return ((immediate >> 4) as usize + 1, (immediate & 0x0F) as usize + 1);
+}
+
+// absolute targets of a relative jump at `position`, the offsets count from
+// the end of the instruction
+pub fn jump_targets(code: &[u8], position: usize) -> Vec<isize> {
+    let end: isize = (position + 1 + immediate_size(code, position)) as isize;
+    match code[position] {
+        RJUMP | RJUMPI => vec![end + read_i16(code, position + 1)],
+        RJUMPV => (0..=code[position + 1] as usize)
+            .map(|case| end + read_i16(code, position + 2 + case * 2))
+            .collect(),
+        _ => Vec::new()
+    }
+}
+
+fn is_terminating(opcode: u8) -> bool {
+    return matches!(opcode, STOP | RETURN | REVERT | INVALID | RETF | JUMPF | RETURNCONTRACT | RJUMP);
+}
+
+// items an instruction takes from and leaves on the stack. Legacy instructions
+// that inspect code or gas, or jump to computed destinations, do not exist in
+// EOF. CALLF, RETF and JUMPF depend on the section types and are not listed
+fn stack_io(opcode: u8, immediate: u8) -> Option<(usize, usize)> {
+    let io: (usize, usize) = match opcode {
+        0x00 | 0x5B | INVALID | RJUMP => (0, 0),
+        0x01..=0x07 | 0x0A | 0x0B | 0x10..=0x14 | 0x16..=0x18 | 0x1A..=0x1D | 0x20 => (2, 1),
+        0x08 | 0x09 => (3, 1),
+        0x15 | 0x19 | 0x31 | 0x35 | 0x40 | 0x49 | 0x51 | 0x54 | DATALOAD => (1, 1),
+        0x30 | 0x32..=0x34 | 0x36 | 0x3A | 0x3D | 0x41..=0x48 | 0x4A | 0x59 => (0, 1),
+        0x5F..=0x7F | DATALOADN | DATASIZE => (0, 1),
+        0x37 | 0x3E | DATACOPY => (3, 0),
+        0x50 | RJUMPI | RJUMPV => (1, 0),
+        0x52 | 0x53 | 0x55 | RETURN | REVERT | RETURNCONTRACT => (2, 0),
+        0x80..=0x8F => ((opcode - 0x7F) as usize, (opcode - 0x7E) as usize),
+        0x90..=0x9F => ((opcode - 0x8E) as usize, (opcode - 0x8E) as usize),
+        0xA0..=0xA4 => ((opcode - 0x9E) as usize, 0),
+        DUPN => (immediate as usize + 1, immediate as usize + 2),
+        SWAPN => (immediate as usize + 2, immediate as usize + 2),
+        EXCHANGE => {
+            let (n, m): (usize, usize) = exchange_operands(immediate);
+            (n + m + 1, n + m + 1)
+        },
+        EOFCREATE | EXTCALL => (4, 1),
+        _ => return None
+    };
+    return Some(io);
+}
+
+
+impl Container {
+    pub fn decode(bytes: &[u8]) -> Result<Container, EofError> {
+        let (container, size): (Container, usize) = Container::decode_prefix(bytes)?;
+        if size < bytes.len() {
+            return Err(EofError::TrailingBytes);
+        }
+        return Ok(container);
+    }
+
+    // the container at the start of `bytes` and how many bytes it spans. The
+    // data section ends at its declared size, or earlier with the bytes
+    pub fn decode_prefix(bytes: &[u8]) -> Result<(Container, usize), EofError> {
+        if !is_eof(bytes) {
+            return Err(EofError::InvalidMagic);
+        }
+        let mut reader: Reader = Reader { bytes: bytes, position: 2 };
+        let version: u8 = reader.u8()?;
+        if version != VERSION {
+            return Err(EofError::InvalidVersion(version));
+        }
+
+        reader.kind(KIND_TYPES)?;
+        let types_size: usize = reader.u16()?;
+        reader.kind(KIND_CODE)?;
+        let code_sizes: Vec<usize> = reader.sizes(false)?;
+        if code_sizes.len() > MAX_CODE_SECTIONS {
+            return Err(EofError::TooManyCodeSections(code_sizes.len()));
+        }
+        let mut container_sizes: Vec<usize> = Vec::new();
+        if bytes.get(reader.position) == Some(&KIND_CONTAINER) {
+            reader.kind(KIND_CONTAINER)?;
+            container_sizes = reader.sizes(true)?;
+            if container_sizes.len() > MAX_CONTAINER_SECTIONS {
+                return Err(EofError::TooManyContainers(container_sizes.len()));
+            }
+        }
+        reader.kind(KIND_DATA)?;
+        let data_size: usize = reader.u16()?;
+        reader.kind(TERMINATOR)?;
+
+        if types_size != code_sizes.len() * 4 {
+            return Err(EofError::InvalidTypesSize(types_size));
+        }
+        let types: Vec<FunctionType> = reader.take(types_size)?.chunks_exact(4).map(|chunk| FunctionType {
+            inputs: chunk[0],
+            outputs: chunk[1],
+            max_stack_increase: u16::from_be_bytes([chunk[2], chunk[3]])
+        }).collect();
+
+        let mut code_sections: Vec<Vec<u8>> = Vec::with_capacity(code_sizes.len());
+        for size in code_sizes {
+            code_sections.push(reader.take(size)?.to_vec());
+        }
+        let mut container_sections: Vec<Vec<u8>> = Vec::with_capacity(container_sizes.len());
+        for size in container_sizes {
+            container_sections.push(reader.take(size)?.to_vec());
+        }
+
+        let end: usize = bytes.len().min(reader.position + data_size);
+        let container: Container = Container {
+            types: types,
+            code_sections: code_sections,
+            container_sections: container_sections,
+            data: bytes[reader.position..end].to_vec(),
+            data_size: data_size
+        };
+        return Ok((container, end));
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(KIND_TYPES);
+        bytes.extend((self.types.len() as u16 * 4).to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend((self.code_sections.len() as u16).to_be_bytes());
+        for code in &self.code_sections {
+            bytes.extend((code.len() as u16).to_be_bytes());
+        }
+        if !self.container_sections.is_empty() {
+            bytes.push(KIND_CONTAINER);
+            bytes.extend((self.container_sections.len() as u16).to_be_bytes());
+            for container in &self.container_sections {
+                bytes.extend((container.len() as u32).to_be_bytes());
+            }
+        }
+        bytes.push(KIND_DATA);
+        bytes.extend((self.data_size as u16).to_be_bytes());
+        bytes.push(TERMINATOR);
+
+        for function in &self.types {
+            bytes.push(function.inputs);
+            bytes.push(function.outputs);
+            bytes.extend(function.max_stack_increase.to_be_bytes());
+        }
+        for code in &self.code_sections {
+            bytes.extend(code);
+        }
+        for container in &self.container_sections {
+            bytes.extend(container);
+        }
+        bytes.extend(&self.data);
+        return bytes;
+    }
+
+    // decodes and validates a top level container
+    pub fn validated(bytes: &[u8], kind: ContainerKind) -> Result<Container, EofError> {
+        let container: Container = Container::decode(bytes)?;
+        container.validate(kind, false)?;
+        return Ok(container);
+    }
+
+    // runtime subcontainers may leave their data short, RETURNCONTRACT fills it in
+    fn validate(&self, kind: ContainerKind, partial_data: bool) -> Result<(), EofError> {
+        if !partial_data && self.data.len() < self.data_size {
+            return Err(EofError::TruncatedData);
+        }
+
+        let first: &FunctionType = &self.types[0];
+        if first.inputs != 0 || first.outputs != NON_RETURNING {
+            return Err(EofError::InvalidSectionZeroType);
+        }
+        for (section, function) in self.types.iter().enumerate() {
+            if function.inputs > MAX_IO ||
+               (function.outputs > MAX_IO && function.outputs != NON_RETURNING) ||
+               function.max_stack_increase > MAX_STACK_INCREASE {
+                return Err(EofError::InvalidType(section));
+            }
+            if function.inputs as usize + function.max_stack_increase as usize > MAX_STACK_HEIGHT {
+                return Err(EofError::StackOverflow(section));
+            }
+        }
+
+        // every section has to be reachable from the first one through CALLF and JUMPF
+        let mut references: Vec<Option<ContainerKind>> = vec![None; self.container_sections.len()];
+        let mut callees: Vec<Vec<usize>> = Vec::with_capacity(self.code_sections.len());
+        for section in 0..self.code_sections.len() {
+            callees.push(self.validate_instructions(section, kind, &mut references)?);
+            self.validate_stack(section)?;
+        }
+        let mut reached: Vec<bool> = vec![false; self.code_sections.len()];
+        let mut pending: Vec<usize> = vec![0];
+        reached[0] = true;
+        while let Some(section) = pending.pop() {
+            for callee in &callees[section] {
+                if !reached[*callee] {
+                    reached[*callee] = true;
+                    pending.push(*callee);
+                }
+            }
+        }
+        if let Some(section) = reached.iter().position(|reached| !reached) {
+            return Err(EofError::UnreachableCodeSection(section));
+        }
+
+        for (index, bytes) in self.container_sections.iter().enumerate() {
+            let kind: ContainerKind = references[index].ok_or(EofError::UnreferencedContainer(index))?;
+            Container::decode(bytes)?.validate(kind, kind == ContainerKind::Runtime)?;
+        }
+        return Ok(());
+    }
+
+    // checks every instruction and its immediates, returns the sections it calls or jumps to
+    fn validate_instructions(&self, section: usize, kind: ContainerKind, references: &mut [Option<ContainerKind>]) -> Result<Vec<usize>, EofError> {
+        let code: &[u8] = &self.code_sections[section];
+        let returning: bool = self.types[section].outputs != NON_RETURNING;
+        let mut returns: bool = false;
+        let mut callees: Vec<usize> = Vec::new();
+        let mut starts: Vec<bool> = vec![false; code.len()];
+        let mut jumps: Vec<(usize, isize)> = Vec::new();
+
+        let mut position: usize = 0;
+        while position < code.len() {
+            let opcode: u8 = code[position];
+            starts[position] = true;
+            if !matches!(opcode, CALLF | RETF | JUMPF) && stack_io(opcode, 0).is_none() {
+                return Err(EofError::UndefinedInstruction { section: section, offset: position, opcode: opcode });
+            }
+            let next: usize = position + 1 + immediate_size(code, position);
+            if next > code.len() {
+                return Err(EofError::TruncatedImmediate { section: section, offset: position });
+            }
+
+            match opcode {
+                STOP | RETURN if kind == ContainerKind::Initcode => return Err(EofError::IncompatibleContainerKind),
+                RETURNCONTRACT if kind == ContainerKind::Runtime => return Err(EofError::IncompatibleContainerKind),
+                RJUMP | RJUMPI | RJUMPV => {
+                    for target in jump_targets(code, position) {
+                        jumps.push((position, target));
+                    }
+                },
+                CALLF | JUMPF => {
+                    let target: usize = read_u16(code, position + 1);
+                    let function: &FunctionType = self.types.get(target).ok_or(EofError::InvalidCodeSection(target))?;
+                    let target_returns: bool = function.outputs != NON_RETURNING;
+                    if opcode == CALLF && !target_returns {
+                        return Err(EofError::CallToNonReturning { section: section, offset: position });
+                    }
+                    if opcode == JUMPF && target_returns {
+                        if !returning || function.outputs > self.types[section].outputs {
+                            return Err(EofError::ReturningMismatch(section));
+                        }
+                        returns = true;
+                    }
+                    callees.push(target);
+                },
+                RETF => {
+                    if !returning {
+                        return Err(EofError::ReturningMismatch(section));
+                    }
+                    returns = true;
+                },
+                DATALOADN if read_u16(code, position + 1) + 32 > self.data_size => {
+                    return Err(EofError::InvalidDataOffset { section: section, offset: position });
+                },
+                EOFCREATE | RETURNCONTRACT => {
+                    let index: usize = code[position + 1] as usize;
+                    let used_as: ContainerKind = if opcode == EOFCREATE { ContainerKind::Initcode } else { ContainerKind::Runtime };
+                    match references.get(index) {
+                        None => return Err(EofError::InvalidContainerIndex(index)),
+                        Some(Some(other)) if *other != used_as => return Err(EofError::IncompatibleContainerKind),
+                        Some(_) => references[index] = Some(used_as)
+                    }
+                },
+                _ => {}
+            }
+            position = next;
+        }
+
+        if returning && !returns {
+            return Err(EofError::ReturningMismatch(section));
+        }
+        for (offset, target) in jumps {
+            if target < 0 || target as usize >= code.len() || !starts[target as usize] {
+                return Err(EofError::InvalidJumpDestination { section: section, offset: offset });
+            }
+        }
+        return Ok(callees);
+    }
+
+    // EIP-5450: tracks the range of stack heights every instruction can see,
+    // in a single pass since jumps backwards must agree with what is already known
+    fn validate_stack(&self, section: usize) -> Result<(), EofError> {
+        let code: &[u8] = &self.code_sections[section];
+        let function: &FunctionType = &self.types[section];
+        let inputs: usize = function.inputs as usize;
+        let mut heights: Vec<Option<(usize, usize)>> = vec![None; code.len()];
+        heights[0] = Some((inputs, inputs));
+        let mut max_height: usize = inputs;
+
+        let mut position: usize = 0;
+        while position < code.len() {
+            let (min, max): (usize, usize) = heights[position]
+                .ok_or(EofError::UnreachableCode { section: section, offset: position })?;
+            let opcode: u8 = code[position];
+            let next: usize = position + 1 + immediate_size(code, position);
+
+            let (taken, pushed): (usize, usize) = match opcode {
+                CALLF | JUMPF => {
+                    let target: &FunctionType = &self.types[read_u16(code, position + 1)];
+                    if max + target.max_stack_increase as usize > MAX_STACK_HEIGHT {
+                        return Err(EofError::StackOverflow(section));
+                    }
+                    if opcode == JUMPF && target.outputs != NON_RETURNING {
+                        // the target returns to our caller in our place
+                        let expected: usize = function.outputs as usize + target.inputs as usize - target.outputs as usize;
+                        if min != max || max != expected {
+                            return Err(EofError::InvalidStackHeight { section: section, offset: position });
+                        }
+                    }
+                    if opcode == CALLF {
+                        (target.inputs as usize, target.outputs as usize)
+                    } else {
+                        (target.inputs as usize, 0)
+                    }
+                },
+                RETF => {
+                    if min != max || max != function.outputs as usize {
+                        return Err(EofError::InvalidStackHeight { section: section, offset: position });
+                    }
+                    (0, 0)
+                },
+                _ => stack_io(opcode, code.get(position + 1).copied().unwrap_or(0)).unwrap()
+            };
+            if min < taken {
+                return Err(EofError::StackUnderflow { section: section, offset: position });
+            }
+            let (next_min, next_max): (usize, usize) = (min - taken + pushed, max - taken + pushed);
+            max_height = max_height.max(next_max);
+
+            let mut successors: Vec<usize> = jump_targets(code, position).iter().map(|target| *target as usize).collect();
+            if !is_terminating(opcode) {
+                successors.push(next);
+            }
+            for successor in successors {
+                if successor >= code.len() {
+                    return Err(EofError::NoTerminatingInstruction(section));
+                }
+                heights[successor] = match heights[successor] {
+                    None => Some((next_min, next_max)),
+                    Some((known_min, known_max)) if successor > position => Some((known_min.min(next_min), known_max.max(next_max))),
+                    Some(known) if known == (next_min, next_max) => Some(known),
+                    Some(_) => return Err(EofError::InvalidStackHeight { section: section, offset: position })
+                };
+            }
+            position = next;
+        }
+
+        if max_height > MAX_STACK_HEIGHT {
+            return Err(EofError::StackOverflow(section));
+        }
+        if max_height - inputs != function.max_stack_increase as usize {
+            return Err(EofError::InvalidMaxStackIncrease(section));
+        }
+        return Ok(());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a container from (inputs, outputs, max_stack_increase) types and hex code sections
+    fn container(types: &[(u8, u8, u16)], code: &[&str], containers: Vec<Vec<u8>>, data: Vec<u8>) -> Container {
+        return Container {
+            types: types.iter().map(|(inputs, outputs, max_stack_increase)| FunctionType {
+                inputs: *inputs,
+                outputs: *outputs,
+                max_stack_increase: *max_stack_increase
+            }).collect(),
+            code_sections: code.iter().map(|code| hex::decode(code).unwrap()).collect(),
+            container_sections: containers,
+            data_size: data.len(),
+            data: data
+        };
+    }
+
+    // a runtime container with a single non-returning section
+    fn runtime(code: &str, max_stack_increase: u16) -> Vec<u8> {
+        return container(&[(0, NON_RETURNING, max_stack_increase)], &[code], Vec::new(), Vec::new()).encode();
+    }
+
+    fn check(kind: ContainerKind, cases: &[(Vec<u8>, Result<(), EofError>)]) {
+        for (i, (bytes, expected)) in cases.iter().enumerate() {
+            let result: Result<(), EofError> = Container::validated(bytes, kind).map(|_| ());
+            assert_eq!(&result, expected, "case {}: {}", i, hex::encode(bytes));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let original: Container = container(&[(0, NON_RETURNING, 2), (1, 1, 1)], &["6007e300015f5500", "8001e4"], Vec::new(), vec![0x11; 4]);
+        let bytes: Vec<u8> = original.encode();
+        assert_eq!(Container::decode(&bytes), Ok(original));
+
+        let mut with_calldata: Vec<u8> = bytes.clone();
+        with_calldata.extend([1, 2, 3]);
+        let (_, size): (Container, usize) = Container::decode_prefix(&with_calldata).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(Container::decode(&with_calldata), Err(EofError::TrailingBytes));
+    }
+
+    #[test]
+    fn test_valid() {
+        let returning: Vec<u8> = container(&[(0, NON_RETURNING, 2), (1, 1, 1)], &["6007e300015f5500", "8001e4"], Vec::new(), Vec::new()).encode();
+        let jumps: Vec<u8> = runtime("6001e10001fe00", 1);
+        let table: Vec<u8> = runtime("5fe2010001000200fe00", 1);
+        let data: Vec<u8> = container(&[(0, NON_RETURNING, 1)], &["d1000000"], Vec::new(), vec![0; 32]).encode();
+        check(ContainerKind::Runtime, &[
+            (runtime("00", 0), Ok(())),
+            (returning, Ok(())),
+            (jumps, Ok(())),
+            (table, Ok(())),
+            (data, Ok(())),
+        ]);
+
+        let init: Vec<u8> = container(&[(0, NON_RETURNING, 2)], &["5f5fee00"], vec![runtime("00", 0)], Vec::new()).encode();
+        let factory: Vec<u8> = container(&[(0, NON_RETURNING, 4)], &["5f5f5f5fec005000"], vec![init.clone()], Vec::new()).encode();
+        check(ContainerKind::Initcode, &[(init, Ok(()))]);
+        check(ContainerKind::Runtime, &[(factory, Ok(()))]);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let valid: Vec<u8> = runtime("00", 0);
+        let mut truncated_data: Container = container(&[(0, NON_RETURNING, 0)], &["00"], Vec::new(), Vec::new());
+        truncated_data.data_size = 2;
+        check(ContainerKind::Runtime, &[
+            (Vec::new(), Err(EofError::InvalidMagic)),
+            (vec![0xEF, 0x01, 0x01], Err(EofError::InvalidMagic)),
+            ([&valid[..2], &[0x02], &valid[3..]].concat(), Err(EofError::InvalidVersion(2))),
+            (valid[..valid.len() - 1].to_vec(), Err(EofError::UnexpectedEnd)),
+            ([&valid[..], &[0x00]].concat(), Err(EofError::TrailingBytes)),
+            (hex::decode("ef00010100040200000400000000800000").unwrap(), Err(EofError::ZeroSectionSize)),
+            (hex::decode("ef0001010008020001000104000000008000000000").unwrap(), Err(EofError::InvalidTypesSize(8))),
+            (hex::decode("ef000102000402000100010400000000800000").unwrap(), Err(EofError::MissingSection(KIND_TYPES))),
+            (truncated_data.encode(), Err(EofError::TruncatedData)),
+            (container(&[(1, NON_RETURNING, 0)], &["00"], Vec::new(), Vec::new()).encode(), Err(EofError::InvalidSectionZeroType)),
+            (container(&[(0, 0, 0)], &["00"], Vec::new(), Vec::new()).encode(), Err(EofError::InvalidSectionZeroType)),
+            (container(&[(0, NON_RETURNING, 0), (0x80, 0, 0)], &["00", "e4"], Vec::new(), Vec::new()).encode(), Err(EofError::InvalidType(1))),
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_code() {
+        check(ContainerKind::Runtime, &[
+            // JUMP, SELFDESTRUCT and friends do not exist in EOF
+            (runtime("600156", 1), Err(EofError::UndefinedInstruction { section: 0, offset: 2, opcode: 0x56 })),
+            (runtime("ff", 0), Err(EofError::UndefinedInstruction { section: 0, offset: 0, opcode: 0xFF })),
+            (runtime("61ff", 1), Err(EofError::TruncatedImmediate { section: 0, offset: 0 })),
+            (runtime("6001e1fffe00", 1), Err(EofError::InvalidJumpDestination { section: 0, offset: 2 })),
+            (runtime("e0000300", 0), Err(EofError::InvalidJumpDestination { section: 0, offset: 0 })),
+            (runtime("0100", 0), Err(EofError::StackUnderflow { section: 0, offset: 0 })),
+            (runtime("600100", 2), Err(EofError::InvalidMaxStackIncrease(0))),
+            (runtime("6001", 1), Err(EofError::NoTerminatingInstruction(0))),
+            (runtime("5f5fe0fffc", 2), Err(EofError::InvalidStackHeight { section: 0, offset: 2 })),
+            (runtime("00fe", 0), Err(EofError::UnreachableCode { section: 0, offset: 1 })),
+            (runtime("d1000000", 1), Err(EofError::InvalidDataOffset { section: 0, offset: 0 })),
+            // RETF in the first section, which never returns
+            (runtime("e4", 0), Err(EofError::ReturningMismatch(0))),
+            (runtime("e3000100", 0), Err(EofError::InvalidCodeSection(1))),
+            (runtime("e5000100", 0), Err(EofError::InvalidCodeSection(1))),
+            (container(&[(0, NON_RETURNING, 0), (0, NON_RETURNING, 0)], &["e3000100", "00"], Vec::new(), Vec::new()).encode(),
+                Err(EofError::CallToNonReturning { section: 0, offset: 0 })),
+            (container(&[(0, NON_RETURNING, 0), (0, NON_RETURNING, 0)], &["00", "00"], Vec::new(), Vec::new()).encode(),
+                Err(EofError::UnreachableCodeSection(1))),
+            (container(&[(0, NON_RETURNING, 0)], &["00"], vec![runtime("00", 0)], Vec::new()).encode(),
+                Err(EofError::UnreferencedContainer(0))),
+            (container(&[(0, NON_RETURNING, 4)], &["5f5f5f5fec015000"], vec![runtime("00", 0)], Vec::new()).encode(),
+                Err(EofError::InvalidContainerIndex(1))),
+        ]);
+    }
+
+    #[test]
+    fn test_container_kinds() {
+        let init: Vec<u8> = container(&[(0, NON_RETURNING, 2)], &["5f5fee00"], vec![runtime("00", 0)], Vec::new()).encode();
+        // init code ends in RETURNCONTRACT, runtime code cannot use it
+        check(ContainerKind::Initcode, &[
+            (runtime("00", 0), Err(EofError::IncompatibleContainerKind)),
+            (runtime("5f5ff3", 2), Err(EofError::IncompatibleContainerKind)),
+        ]);
+        check(ContainerKind::Runtime, &[
+            (init.clone(), Err(EofError::IncompatibleContainerKind)),
+        ]);
+        // subcontainers are validated as what they are used for
+        let factory: Vec<u8> = container(&[(0, NON_RETURNING, 4)], &["5f5f5f5fec005000"], vec![runtime("00", 0)], Vec::new()).encode();
+        let bad_sub: Vec<u8> = container(&[(0, NON_RETURNING, 4)], &["5f5f5f5fec005000"], vec![runtime("ff", 0)], Vec::new()).encode();
+        check(ContainerKind::Runtime, &[
+            (factory, Err(EofError::IncompatibleContainerKind)),
+            (bad_sub, Err(EofError::UndefinedInstruction { section: 0, offset: 0, opcode: 0xFF })),
+        ]);
+    }
+}
@@ -1,14 +1,61 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 use ethnum::{U256, I256};
 use sha3::{Digest, Keccak256};
-use std::collections::HashMap;
-use lazy_static::lazy_static;
 
+use crate::arithmetic;
 use crate::context;
 use crate::blockchain;
+use crate::eof;
+use crate::hardfork::Hardfork;
 use crate::utils;
 use crate::instructions;
 
 
+pub const CALL_DEPTH_LIMIT: usize = 1024;
+pub const MAX_CODE_SIZE: usize = 0x6000; // EIP-170
+pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE; // EIP-3860
+const INITCODE_WORD_GAS: u64 = 2;
+const KECCAK_WORD_GAS: u64 = 6;
+// per byte of the exponent, raised by EIP-160
+const EXP_BYTE_GAS: u64 = 10;
+const EXP_BYTE_GAS_EIP160: u64 = 50;
+const CODE_DEPOSIT_GAS: u64 = 200;
+const NEW_ACCOUNT_GAS: u64 = 25000;
+const LOG_DATA_GAS: u64 = 8;
+const COPY_WORD_GAS: u64 = 3;
+const MEMORY_WORD_GAS: u64 = 3;
+// memory past this could never be paid for
+const MAX_MEMORY_SIZE: u64 = u32::MAX as u64;
+const CALL_VALUE_GAS: u64 = 9000;
+const CALL_STIPEND: u64 = 2300;
+const RETURN_STACK_LIMIT: usize = 1024;
+// EIP-7069: EXTCALL keeps at least this much for the caller and gives up
+// below the callee minimum instead of failing
+const MIN_RETAINED_GAS: u64 = 5000;
+const MIN_CALLEE_GAS: u64 = 2300;
+
+
+// the legacy calls differ in whose code runs on which account, and in
+// whether value moves
+#[derive(Clone, Copy, PartialEq)]
+enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall
+}
+
+
+// outcome of a call or create frame
+pub struct FrameResult {
+    pub success: bool,
+    pub reverted: bool,
+    pub output: Vec<u8>,
+    pub gas_left: U256
+}
+
+
 pub struct EVM<'a> {
     blockchain: &'a mut blockchain::BlockChain,
     pc: usize,
@@ -16,12 +63,18 @@ pub struct EVM<'a> {
     memory: Vec<u8>,
     returndata: Vec<u8>,
     ctx: context::CTX,
-    success: bool
+    depth: usize,
+    success: bool,
+    reverted: bool,
+    // EOF frames run one code section at a time, `ctx.code` holds the current one
+    eof: Option<eof::Container>,
+    section: usize,
+    return_stack: Vec<(usize, usize)>
 }
 
 
 impl <'a>EVM<'a> {
-    pub fn new(mut blockchain: &'a mut blockchain::BlockChain, ctx: context::CTX) -> EVM {
+    pub fn new(blockchain: &'a mut blockchain::BlockChain, ctx: context::CTX) -> EVM<'a> {
         return EVM {
             blockchain: blockchain,
             pc: 0,
@@ -29,7 +82,30 @@ impl <'a>EVM<'a> {
             memory: Vec::new(),
             returndata: Vec::new(),
             ctx: ctx,
-            success: true
+            depth: 0,
+            success: true,
+            reverted: false,
+            eof: None,
+            section: 0,
+            return_stack: Vec::new()
+        };
+    }
+
+    pub fn with_depth(blockchain: &'a mut blockchain::BlockChain, ctx: context::CTX, depth: usize) -> EVM<'a> {
+        let mut vm = EVM::new(blockchain, ctx);
+        vm.depth = depth;
+        return vm;
+    }
+
+    pub fn execute(&mut self) -> FrameResult {
+        let success: bool;
+        let output: Vec<u8>;
+        (success, output) = self.run();
+        return FrameResult {
+            success: success,
+            reverted: self.reverted,
+            output: output,
+            gas_left: self.ctx.msg.gas
         };
     }
 
@@ -37,25 +113,42 @@ impl <'a>EVM<'a> {
         println!("---- New Context ---");
         let mut returndata: Vec<u8> = Vec::new();
         let mut opcode: u8;
+        // EOF code only runs once it validates, init code arrives already
+        // validated from execute_create
+        if self.eof.is_none() && self.blockchain.is_eof_enabled() && eof::is_eof(&self.ctx.code) {
+            match eof::Container::validated(&self.ctx.code, eof::ContainerKind::Runtime) {
+                Ok(container) => self.eof = Some(container),
+                Err(_) => self.success = false
+            }
+        }
+        if let Some(container) = &self.eof {
+            self.ctx.code = container.code_sections[0].clone();
+        }
         while self.success && self.pc < self.ctx.code.len() {
             opcode = self.ctx.code[self.pc];
             self.pc += 1;
 
-            let instruction: &instructions::Instruction = instructions::INSTRUCTIONS.get(&opcode).unwrap();
+            let instruction: &instructions::Instruction = match instructions::INSTRUCTIONS.get(&opcode) {
+                Some(instruction) => instruction,
+                None => { // undefined instruction
+                    self.success = false;
+                    break;
+                }
+            };
 
             // burn gas for the instruction
-            let gas_cost: U256 = U256::new(instruction.gas_cost.try_into().unwrap());
+            let gas_cost: U256 = U256::new(instruction.gas_cost.into());
             if self.ctx.msg.gas >= gas_cost {
                 self.ctx.msg.gas -= gas_cost;
             } else {
                 self.success = false;
-                returndata = "out of gas".as_bytes().to_vec();
                 break;
             }
 
             // print the instruction
             if instruction.operands > 0 {
-                let data = &self.ctx.code[self.pc..self.pc + instruction.operands];
+                let end: usize = (self.pc + instruction.operands).min(self.ctx.code.len());
+                let data = &self.ctx.code[self.pc..end];
                 let hex_data = hex::encode(data);
                 println!("{} 0x{} \t// {:#02X} {:02X?}", instruction.name, hex_data, opcode, data);
             } else {
@@ -113,11 +206,13 @@ impl <'a>EVM<'a> {
                 0x41 => self.opcode_coinbase(),
                 0x42 => self.opcode_timestamp(),
                 0x43 => self.opcode_number(),
-                0x44 => self.opcode_difficulty(),
+                0x44 => self.opcode_prevrandao(),
                 0x45 => self.opcode_gaslimit(),
                 0x46 => self.opcode_chainid(),
                 0x47 => self.opcode_selfbalance(),
                 0x48 => self.opcode_basefee(),
+                0x49 if self.blockchain.get_hardfork().is_enabled(Hardfork::Cancun) => self.opcode_blobhash(),
+                0x4A if self.blockchain.get_hardfork().is_enabled(Hardfork::Cancun) => self.opcode_blobbasefee(),
                 0x50 => self.opcode_pop(),
                 0x51 => self.opcode_mload(),
                 0x52 => self.opcode_mstore(),
@@ -195,11 +290,29 @@ impl <'a>EVM<'a> {
                 0x9D => self.opcode_swap(14),
                 0x9E => self.opcode_swap(15),
                 0x9F => self.opcode_swap(16),
-                0xA0 => self.opcode_log0(),
-                0xA1 => self.opcode_log1(),
-                0xA2 => self.opcode_log2(),
-                0xA3 => self.opcode_log3(),
-                0xA4 => self.opcode_log4(),
+                0xA0 => self.opcode_log(0),
+                0xA1 => self.opcode_log(1),
+                0xA2 => self.opcode_log(2),
+                0xA3 => self.opcode_log(3),
+                0xA4 => self.opcode_log(4),
+                0xD0 if self.eof.is_some() => self.opcode_dataload(),
+                0xD1 if self.eof.is_some() => self.opcode_dataloadn(),
+                0xD2 if self.eof.is_some() => self.opcode_datasize(),
+                0xD3 if self.eof.is_some() => self.opcode_datacopy(),
+                0xE0 if self.eof.is_some() => self.opcode_rjump(),
+                0xE1 if self.eof.is_some() => self.opcode_rjumpi(),
+                0xE2 if self.eof.is_some() => self.opcode_rjumpv(),
+                0xE3 if self.eof.is_some() => self.opcode_callf(),
+                0xE4 if self.eof.is_some() => self.opcode_retf(),
+                0xE5 if self.eof.is_some() => self.opcode_jumpf(),
+                0xE6 if self.eof.is_some() => self.opcode_dupn(),
+                0xE7 if self.eof.is_some() => self.opcode_swapn(),
+                0xE8 if self.eof.is_some() => self.opcode_exchange(),
+                0xEC if self.eof.is_some() => self.opcode_eofcreate(),
+                0xEE if self.eof.is_some() => {
+                    returndata = self.opcode_returncontract();
+                    break;
+                },
                 0xF0 => self.opcode_create(),
                 0xF1 => self.opcode_call(),
                 0xF2 => self.opcode_callcode(),
@@ -210,22 +323,34 @@ impl <'a>EVM<'a> {
 
                 0xF4 => self.opcode_delegatecall(),
                 0xF5 => self.opcode_create2(),
+                0xF8 if self.eof.is_some() => self.opcode_extcall(),
                 0xFA => self.opcode_staticcall(),
                 0xFD => {
                     returndata = self.opcode_revert();
                     self.success = false;
+                    self.reverted = true;
                 },
                 0xFE => {
                     returndata = self.opcode_invalid();
                     self.success = false;
                 },
-                0xFF => self.opcode_selfdestruct(),
+                0xFF => {
+                    self.opcode_selfdestruct();
+                    break;
+                },
                 _ => { // invalid instruction
                     self.success = false;
                 }
             }
         }
 
+        // exceptional halts consume all the gas given to the frame and return
+        // nothing, REVERT does not
+        if !self.success && !self.reverted {
+            self.ctx.msg.gas = U256::new(0);
+            returndata = Vec::new();
+        }
+
         println!("-----------");
         return (self.success, returndata);
     }
@@ -248,6 +373,52 @@ impl <'a>EVM<'a> {
         self.stack.push(val);
     }
 
+    fn use_gas(&mut self, amount: U256) -> bool {
+        if self.ctx.msg.gas < amount {
+            self.ctx.msg.gas = U256::new(0);
+            self.success = false;
+            return false;
+        }
+        self.ctx.msg.gas -= amount;
+        return true;
+    }
+
+    // EIP-150: a child frame gets at most all but one 64th of the remaining gas
+    fn all_but_one_64th(&self) -> U256 {
+        return self.ctx.msg.gas - self.ctx.msg.gas / 64;
+    }
+
+    // charges for growing memory to cover `size` bytes from `offset`, then
+    // grows it. Offsets too large to ever pay for run out of gas
+    fn expand_memory(&mut self, offset: U256, size: U256) -> bool {
+        if size == 0 {
+            return true;
+        }
+        let (end, overflow): (U256, bool) = offset.overflowing_add(size);
+        if overflow || end > U256::from(MAX_MEMORY_SIZE) {
+            return self.use_gas(U256::MAX);
+        }
+
+        let words: usize = end.as_usize().div_ceil(32);
+        let current: usize = self.memory.len().div_ceil(32);
+        if words > current {
+            if !self.use_gas(memory_cost(words) - memory_cost(current)) {
+                return false;
+            }
+            self.memory.resize(words * 32, 0);
+        }
+        return true;
+    }
+
+    // gas for copying `size` bytes to memory at `dest_offset`
+    fn charge_copy(&mut self, dest_offset: U256, size: U256) -> bool {
+        if !self.expand_memory(dest_offset, size) {
+            return false;
+        }
+        let words: U256 = U256::from(size.as_usize().div_ceil(32) as u64);
+        return self.use_gas(words * U256::from(COPY_WORD_GAS));
+    }
+
     fn memory_load(&mut self, offset: usize, size: usize) -> Vec<u8> {
         if self.memory.len() < offset + size {
             self.memory.resize(offset + size, 0);
@@ -255,29 +426,33 @@ impl <'a>EVM<'a> {
         return self.memory[offset..offset + size].to_vec();
     }
 
+    // `size` bytes of memory from `offset`, once the expansion is paid for.
+    // None when it runs out of gas, an empty read never touches memory
+    fn memory_read(&mut self, offset: U256, size: U256) -> Option<Vec<u8>> {
+        if !self.expand_memory(offset, size) {
+            return None;
+        }
+        if size == 0 {
+            return Some(Vec::new());
+        }
+        return Some(self.memory_load(offset.as_usize(), size.as_usize()));
+    }
+
     fn memory_store(&mut self, offset: usize, data: Vec<u8>) {
         let size: usize = data.len();
         if self.memory.len() < offset + size {
             self.memory.resize(offset + size, 0);
         }
 
-        let i: usize;
-        for i in 0..size {
-            self.memory[offset + i] = data[i];
-        }
+        self.memory[offset..offset + size].copy_from_slice(&data);
     }
 
     fn storage_load(&mut self, address: U256, key: U256) -> U256 {
-        let account: &blockchain::Account = self.blockchain.get_account(address);
-        match account.storage.get(&key) {
-            Some(&value) => value,
-            None => U256::new(0)
-        }
+        return self.blockchain.get_storage(address, key);
     }
 
     fn storage_store(&mut self, address: U256, key: U256, value: U256) {
-        let mut account: &mut blockchain::Account = self.blockchain.get_account(address);
-        account.storage.insert(key, value).unwrap();
+        self.blockchain.set_storage(address, key, value);
     }
 
     fn opcode_stop(&self) {}
@@ -310,7 +485,7 @@ impl <'a>EVM<'a> {
         let a: U256 = self.stack_pop();
         let b: U256 = self.stack_pop();
 
-        let result: U256 = a.wrapping_div(b);
+        let result: U256 = arithmetic::div(a, b);
         self.stack_push(result);
     }
 
@@ -318,7 +493,7 @@ impl <'a>EVM<'a> {
         let a: U256 = self.stack_pop();
         let b: U256 = self.stack_pop();
 
-        let result: U256 = a.wrapping_div(b);
+        let result: U256 = arithmetic::sdiv(a, b);
         self.stack_push(result);
     }
 
@@ -326,7 +501,7 @@ impl <'a>EVM<'a> {
         let a: U256 = self.stack_pop();
         let b: U256 = self.stack_pop();
 
-        let result: U256 = a.wrapping_rem(b);
+        let result: U256 = arithmetic::modulo(a, b);
         self.stack_push(result);
     }
 
@@ -334,25 +509,25 @@ impl <'a>EVM<'a> {
         let a: U256 = self.stack_pop();
         let b: U256 = self.stack_pop();
 
-        let result: U256 = a.wrapping_rem(b);
+        let result: U256 = arithmetic::smod(a, b);
         self.stack_push(result);
     }
 
     fn opcode_addmod(&mut self) {
         let a: U256 = self.stack_pop();
         let b: U256 = self.stack_pop();
-        let N: U256 = self.stack_pop();
+        let n: U256 = self.stack_pop();
 
-        let result: U256 = a.wrapping_add(b).wrapping_rem(N);
+        let result: U256 = arithmetic::addmod(a, b, n);
         self.stack_push(result);
     }
 
     fn opcode_mulmod(&mut self) {
         let a: U256 = self.stack_pop();
         let b: U256 = self.stack_pop();
-        let N: U256 = self.stack_pop();
+        let n: U256 = self.stack_pop();
 
-        let result: U256 = a.wrapping_mul(b).wrapping_rem(N);
+        let result: U256 = arithmetic::mulmod(a, b, n);
         self.stack_push(result);
     }
 
@@ -360,32 +535,26 @@ impl <'a>EVM<'a> {
         let a: U256 = self.stack_pop();
         let b: U256 = self.stack_pop();
 
-        let mut result: U256 = a.wrapping_pow(b.as_u32());
+        let byte_gas: u64 = if self.blockchain.get_hardfork().is_enabled(Hardfork::SpuriousDragon) {
+            EXP_BYTE_GAS_EIP160
+        } else {
+            EXP_BYTE_GAS
+        };
+        let bytes: u64 = (256 - b.leading_zeros() as u64).div_ceil(8);
+        if !self.use_gas(U256::from(bytes * byte_gas)) {
+            return;
+        }
+
+        let result: U256 = arithmetic::exp(a, b);
         self.stack_push(result);
     }
 
     fn opcode_signextend(&mut self) {
-        let b: usize = self.stack_pop().as_usize();
+        let b: U256 = self.stack_pop();
         let x: U256 = self.stack_pop();
 
-        let mut y: U256;
-        if b > 31 {
-            y = x;
-        } else {
-            let x_bytes = &x.to_be_bytes()[31 - b..];
-            let sign_bit = x_bytes[0] >> 7;
-            if sign_bit == 0 {
-                y = U256::from_be_bytes(x_bytes.try_into().unwrap())
-            } else {
-                let num_bytes = 32 - (b + 1);
-                let mut data: Vec<u8> = Vec::new();
-                data.resize(0xFF, b.try_into().unwrap());
-                data.extend(x_bytes);
-                y = U256::from_be_bytes(data.try_into().unwrap());
-            }
-        }
-
-        self.stack_push(y);
+        let result: U256 = arithmetic::signextend(b, x);
+        self.stack_push(result);
     }
 
     fn opcode_lt(&mut self) {
@@ -486,7 +655,7 @@ impl <'a>EVM<'a> {
         let i: U256 = self.stack_pop();
         let x: U256 = self.stack_pop();
 
-        let result = (x >> (31 - i)) & 0xff;
+        let result: U256 = arithmetic::byte(i, x);
         self.stack_push(result);
     }
 
@@ -494,7 +663,7 @@ impl <'a>EVM<'a> {
         let shift: U256 = self.stack_pop();
         let val: U256 = self.stack_pop();
 
-        let result: U256 = val << shift;
+        let result: U256 = arithmetic::shl(shift, val);
         self.stack_push(result);
     }
 
@@ -502,7 +671,7 @@ impl <'a>EVM<'a> {
         let shift: U256 = self.stack_pop();
         let val: U256 = self.stack_pop();
 
-        let result: U256 = val >> shift;
+        let result: U256 = arithmetic::shr(shift, val);
         self.stack_push(result);
     }
 
@@ -510,7 +679,7 @@ impl <'a>EVM<'a> {
         let shift: U256 = self.stack_pop();
         let val: U256 = self.stack_pop();
 
-        let result: U256 = val >> shift;
+        let result: U256 = arithmetic::sar(shift, val);
         self.stack_push(result);
     }
 
@@ -527,14 +696,12 @@ impl <'a>EVM<'a> {
     }
 
     fn opcode_address(&mut self) {
-        let result: U256 = U256::new(0);
         self.stack_push(self.ctx.address);
     }
 
     fn opcode_balance(&mut self) {
         let address: U256 = self.stack_pop();
-        let account: &blockchain::Account = self.blockchain.get_account(address);
-        let balance: U256 = account.balance;
+        let balance: U256 = self.blockchain.get_balance(address);
         self.stack_push(balance);
     }
 
@@ -550,10 +717,11 @@ impl <'a>EVM<'a> {
         self.stack_push(self.ctx.msg.value);
     }
 
+    // reads past the end of the calldata are zero
     fn opcode_calldataload(&mut self) {
-        let offset: usize = self.stack_pop().as_usize();
-        let data: [u8; 32] = self.ctx.msg.calldata[offset..offset + 32].try_into().unwrap();
-        let result: U256 = U256::from_be_bytes(data);
+        let offset: U256 = self.stack_pop();
+        let data: Vec<u8> = padded_slice(&self.ctx.msg.calldata, offset, 32);
+        let result: U256 = U256::from_be_bytes(data.try_into().unwrap());
         self.stack_push(result);
     }
 
@@ -563,12 +731,15 @@ impl <'a>EVM<'a> {
     }
 
     fn opcode_calldatacopy(&mut self) {
-        let dest_offset = self.stack_pop().as_usize();
-        let offset = self.stack_pop().as_usize();
-        let size = self.stack_pop().as_usize();
+        let dest_offset: U256 = self.stack_pop();
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
 
-        let data = &self.ctx.msg.calldata[offset..offset + size];
-        self.memory_store(offset, data.to_vec()); 
+        if size == 0 || !self.charge_copy(dest_offset, size) {
+            return;
+        }
+        let data: Vec<u8> = padded_slice(&self.ctx.msg.calldata, offset, size.as_usize());
+        self.memory_store(dest_offset.as_usize(), data);
     }
 
     fn opcode_codesize(&mut self) {
@@ -577,12 +748,15 @@ impl <'a>EVM<'a> {
     }
 
     fn opcode_codecopy(&mut self) {
-        let dest_offset = self.stack_pop().as_usize();
-        let offset = self.stack_pop().as_usize();
-        let size = self.stack_pop().as_usize();
+        let dest_offset: U256 = self.stack_pop();
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
 
-        let data = &self.ctx.code[offset..offset + size];
-        self.memory_store(offset, data.to_vec());
+        if size == 0 || !self.charge_copy(dest_offset, size) {
+            return;
+        }
+        let data: Vec<u8> = padded_slice(&self.ctx.code, offset, size.as_usize());
+        self.memory_store(dest_offset.as_usize(), data);
     }
 
     fn opcode_gasprice(&mut self) {
@@ -590,24 +764,35 @@ impl <'a>EVM<'a> {
         self.stack_push(gasprice);
     }
 
+    // legacy code sees an EOF contract as just its magic
+    fn external_code(&mut self, address: U256) -> Vec<u8> {
+        let code: Vec<u8> = self.blockchain.get_code(address);
+        if self.blockchain.is_eof_enabled() && eof::is_eof(&code) {
+            return eof::MAGIC.to_vec();
+        }
+        return code;
+    }
+
     fn opcode_extcodesize(&mut self) {
         let address: U256 = self.stack_pop();
-        let account: &blockchain::Account = self.blockchain.get_account(address);
+        let code: Vec<u8> = self.external_code(address);
 
-        let extcodesize: U256 = U256::new(account.code.len().try_into().unwrap());
+        let extcodesize: U256 = U256::new(code.len().try_into().unwrap());
         self.stack_push(extcodesize);
     }
 
     fn opcode_extcodecopy(&mut self) {
         let address: U256 = self.stack_pop();
-        let dest_offset: usize = self.stack_pop().as_usize();
-        let offset: usize = self.stack_pop().as_usize();
-        let size: usize = self.stack_pop().as_usize();
+        let dest_offset: U256 = self.stack_pop();
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
 
-        let account: &blockchain::Account = self.blockchain.get_account(address);
-
-        let data = account.code[offset..offset + size].to_vec();
-        self.memory_store(dest_offset, data);
+        if size == 0 || !self.charge_copy(dest_offset, size) {
+            return;
+        }
+        let code: Vec<u8> = self.external_code(address);
+        let data: Vec<u8> = padded_slice(&code, offset, size.as_usize());
+        self.memory_store(dest_offset.as_usize(), data);
     }
 
     fn opcode_returndatasize(&mut self) {
@@ -615,28 +800,46 @@ impl <'a>EVM<'a> {
         self.stack_push(size);
     }
 
+    // reading past the end of the buffer is an exceptional halt (EIP-211)
     fn opcode_returndatacopy(&mut self) {
-        let dest_offset: usize = self.stack_pop().as_usize();
-        let offset: usize = self.stack_pop().as_usize();
-        let size: usize = self.stack_pop().as_usize(); 
+        let dest_offset: U256 = self.stack_pop();
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
 
-        self.memory_store(dest_offset, self.returndata[offset..offset + size].to_vec()); 
+        let (end, overflow): (U256, bool) = offset.overflowing_add(size);
+        if overflow || end > U256::from(self.returndata.len() as u64) {
+            self.success = false;
+            return;
+        }
+        if size == 0 || !self.charge_copy(dest_offset, size) {
+            return;
+        }
+        self.memory_store(dest_offset.as_usize(), self.returndata[offset.as_usize()..end.as_usize()].to_vec());
     }
 
+    // EIP-1052: zero for accounts that do not exist, or are empty since
+    // Spurious Dragon, so an account without code hashes to keccak256("")
     fn opcode_extcodehash(&mut self) {
         let address: U256 = self.stack_pop();
-        let account: &blockchain::Account = self.blockchain.get_account(address);
+        let hardfork: Hardfork = self.blockchain.get_hardfork();
+        if !self.blockchain.has_account(address) ||
+           (hardfork.is_enabled(Hardfork::SpuriousDragon) && self.blockchain.is_empty_account(address)) {
+            self.stack_push(U256::new(0));
+            return;
+        }
+        let code: Vec<u8> = self.external_code(address);
 
         let mut keccak256 = Keccak256::new();
-        keccak256.update(&account.code);
+        keccak256.update(&code);
         let value: U256 = U256::from_be_bytes(keccak256.finalize().into());
 
         self.stack_push(value);
     }
 
     fn opcode_blockhash(&mut self) {
-        let block: blockchain::Block = self.blockchain.get_current_block();
-        self.stack_push(block.blockhash);
+        let number: U256 = self.stack_pop();
+        let blockhash: U256 = self.blockchain.get_block_hash(number);
+        self.stack_push(blockhash);
     }
 
     fn opcode_coinbase(&mut self) {
@@ -654,9 +857,13 @@ impl <'a>EVM<'a> {
         self.stack_push(block.number);
     }
 
-    fn opcode_difficulty(&mut self) {
+    fn opcode_prevrandao(&mut self) {
         let block: blockchain::Block = self.blockchain.get_current_block();
-        self.stack_push(block.difficulty);
+        if self.blockchain.get_hardfork().is_enabled(Hardfork::Paris) {
+            self.stack_push(block.prevrandao);
+        } else {
+            self.stack_push(block.difficulty);
+        }
     }
 
     fn opcode_gaslimit(&mut self) {
@@ -670,14 +877,29 @@ impl <'a>EVM<'a> {
     }
 
     fn opcode_selfbalance(&mut self) {
-        let account: &blockchain::Account = self.blockchain.get_account(self.ctx.address);
-        let balance: U256 = account.balance;
+        let balance: U256 = self.blockchain.get_balance(self.ctx.address);
         self.stack_push(balance);
     }
 
     fn opcode_basefee(&mut self) {
         let block: blockchain::Block = self.blockchain.get_current_block();
-        self.stack_push(block.basefee);
+        self.stack_push(block.basefee.unwrap_or_default());
+    }
+
+    // EIP-4844: the versioned hash of one of the transaction's blobs, zero
+    // past the end of the list
+    fn opcode_blobhash(&mut self) {
+        let index: U256 = self.stack_pop();
+        let hash: U256 = match usize::try_from(index) {
+            Ok(index) => self.ctx.tx.blob_hashes.get(index).copied().unwrap_or_default(),
+            Err(_) => U256::new(0)
+        };
+        self.stack_push(hash);
+    }
+
+    fn opcode_blobbasefee(&mut self) {
+        let block: blockchain::Block = self.blockchain.get_current_block();
+        self.stack_push(block.blob_basefee(self.blockchain.get_hardfork()));
     }
 
     fn opcode_pop(&mut self) {
@@ -726,8 +948,7 @@ impl <'a>EVM<'a> {
            self.ctx.code[counter] == 0x5B { // check for JUMPDEST
             self.pc = counter;
         } else {
-            self.success = false; 
-            self.returndata = "destination is not a JUMPDEST".as_bytes().to_vec();
+            self.success = false;
         }
     }
 
@@ -741,7 +962,6 @@ impl <'a>EVM<'a> {
             self.pc = counter;
         } else {
             self.success = false;
-            self.returndata = "destination is not a JUMPDEST".as_bytes().to_vec();
         }
     }
 
@@ -783,181 +1003,300 @@ impl <'a>EVM<'a> {
         self.stack.swap(length - 1, length - 1 - nth);
     }
 
-    fn opcode_log0(&mut self) {
-        let offset = self.stack_pop().as_usize();
-        let size = self.stack_pop().as_usize();
+    fn opcode_log(&mut self, topics_count: usize) {
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
 
-        let data: Vec<u8> = self.memory_load(offset, size);
-        self.blockchain.add_log0(data);
-    }
-    fn opcode_log1(&mut self) {
-        let offset = self.stack_pop().as_usize();
-        let size = self.stack_pop().as_usize();
-        let topic1 = self.stack_pop();
+        let mut topics: Vec<U256> = Vec::new();
+        for _ in 0..topics_count {
+            topics.push(self.stack_pop());
+        }
+
+        let data: Vec<u8> = match self.memory_read(offset, size) {
+            Some(data) => data,
+            None => return
+        };
+        if !self.use_gas(size * U256::from(LOG_DATA_GAS)) {
+            return;
+        }
 
-        let data: Vec<u8> = self.memory_load(offset, size);
-        self.blockchain.add_log1(data, topic1);
+        let log = blockchain::Log::new(self.ctx.address, topics, data);
+        self.blockchain.add_log(log);
     }
 
-    fn opcode_log2(&mut self) {
-        let offset = self.stack_pop().as_usize();
-        let size = self.stack_pop().as_usize();
-        let topic1 = self.stack_pop();
-        let topic2 = self.stack_pop();
+    fn container(&self) -> &eof::Container {
+        return self.eof.as_ref().unwrap();
+    }
 
-        let data: Vec<u8> = self.memory_load(offset, size);
-        self.blockchain.add_log2(data, topic1, topic2);
+    fn immediate_u16(&self) -> usize {
+        return u16::from_be_bytes([self.ctx.code[self.pc], self.ctx.code[self.pc + 1]]) as usize;
     }
 
-    fn opcode_log3(&mut self) {
-        let offset = self.stack_pop().as_usize();
-        let size = self.stack_pop().as_usize();
-        let topic1 = self.stack_pop();
-        let topic2 = self.stack_pop();
-        let topic3 = self.stack_pop();
+    fn enter_section(&mut self, section: usize, pc: usize) {
+        self.ctx.code = self.container().code_sections[section].clone();
+        self.section = section;
+        self.pc = pc;
+    }
 
-        let data: Vec<u8> = self.memory_load(offset, size);
-        self.blockchain.add_log3(data, topic1, topic2, topic3);
+    fn opcode_dataload(&mut self) {
+        let offset: U256 = self.stack_pop();
+        let data: Vec<u8> = padded_slice(&self.container().data, offset, 32);
+        self.stack_push(U256::from_be_bytes(data.try_into().unwrap()));
     }
 
-    fn opcode_log4(&mut self) {
-        let offset = self.stack_pop().as_usize();
-        let size = self.stack_pop().as_usize();
-        let topic1 = self.stack_pop();
-        let topic2 = self.stack_pop();
-        let topic3 = self.stack_pop();
-        let topic4 = self.stack_pop();
+    fn opcode_dataloadn(&mut self) {
+        let offset: U256 = U256::from(self.immediate_u16() as u64);
+        self.pc += 2;
+        let data: Vec<u8> = padded_slice(&self.container().data, offset, 32);
+        self.stack_push(U256::from_be_bytes(data.try_into().unwrap()));
+    }
 
-        let data: Vec<u8> = self.memory_load(offset, size);
-        self.blockchain.add_log4(data, topic1, topic2, topic3, topic4);
+    fn opcode_datasize(&mut self) {
+        let size: U256 = U256::from(self.container().data.len() as u64);
+        self.stack_push(size);
     }
 
-    fn opcode_create(&mut self) {
-        let value: U256 = self.stack_pop();
-        let offset: usize = self.stack_pop().as_usize();
-        let size: usize = self.stack_pop().as_usize();
+    fn opcode_datacopy(&mut self) {
+        let dest_offset: U256 = self.stack_pop();
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
 
-        let account: &blockchain::Account = self.blockchain.get_account(self.ctx.address);
-        let address = &self.ctx.address.to_be_bytes()[12..32];
+        if size == 0 || !self.charge_copy(dest_offset, size) {
+            return;
+        }
+        let data: Vec<u8> = padded_slice(&self.container().data, offset, size.as_usize());
+        self.memory_store(dest_offset.as_usize(), data);
+    }
 
-        let mut data: Vec<u8> = utils::encode_rlp(address, account.nonce);
-        let mut keccak256 = Keccak256::new();
-        keccak256.update(data);
+    // validation guarantees relative jumps land on an instruction of the section
+    fn opcode_rjump(&mut self) {
+        self.pc = eof::jump_targets(&self.ctx.code, self.pc - 1)[0] as usize;
+    }
 
-        let result = hex::encode(&keccak256.finalize()[12..32]);
-        let mut contract_address: U256 = U256::from_str_radix(&result, 16).unwrap();
+    fn opcode_rjumpi(&mut self) {
+        let condition: U256 = self.stack_pop();
+        if condition == 0 {
+            self.pc += 2;
+            return;
+        }
+        self.opcode_rjump();
+    }
 
-        let calldata: Vec<u8> = Vec::new(); 
-        let bytecode = self.memory_load(offset, size);
-        let gas = U256::new(10000);
+    // jumps through the table, or falls through when the case is past its end
+    fn opcode_rjumpv(&mut self) {
+        let case: U256 = self.stack_pop();
+        let targets: Vec<isize> = eof::jump_targets(&self.ctx.code, self.pc - 1);
+        if case < U256::from(targets.len() as u64) {
+            self.pc = targets[case.as_usize()] as usize;
+        } else {
+            self.pc += eof::immediate_size(&self.ctx.code, self.pc - 1);
+        }
+    }
 
-        let msg = context::MSG::new(
-            self.ctx.address,
-            contract_address,
-            calldata,
-            value,
-            gas
-        );
+    fn opcode_callf(&mut self) {
+        let section: usize = self.immediate_u16();
+        let max_stack_increase: usize = self.container().types[section].max_stack_increase as usize;
+        if self.return_stack.len() >= RETURN_STACK_LIMIT ||
+           self.stack.len() + max_stack_increase > eof::MAX_STACK_HEIGHT {
+            self.success = false;
+            return;
+        }
+        self.return_stack.push((self.section, self.pc + 2));
+        self.enter_section(section, 0);
+    }
 
-        let ctx = context::CTX::new(
-            self.ctx.tx,
-            msg,
-            contract_address,
-            bytecode
-        );
+    fn opcode_retf(&mut self) {
+        let (section, pc): (usize, usize) = self.return_stack.pop().unwrap();
+        self.enter_section(section, pc);
+    }
 
-        let mut vm = EVM::new(self.blockchain, ctx);
+    // a tail call, the target returns straight to our caller
+    fn opcode_jumpf(&mut self) {
+        let section: usize = self.immediate_u16();
+        let max_stack_increase: usize = self.container().types[section].max_stack_increase as usize;
+        if self.stack.len() + max_stack_increase > eof::MAX_STACK_HEIGHT {
+            self.success = false;
+            return;
+        }
+        self.enter_section(section, 0);
+    }
 
-        let success: bool;
-        let returndata: Vec<u8>;
-        (success, returndata) = vm.run();
+    fn opcode_dupn(&mut self) {
+        let n: usize = self.ctx.code[self.pc] as usize + 1;
+        self.pc += 1;
+        self.opcode_dup(n);
+    }
 
-        if success {
-            self.blockchain.add_contract_bin(contract_address, returndata);
-        } else {
-            contract_address = U256::new(0);
-        }
+    fn opcode_swapn(&mut self) {
+        let n: usize = self.ctx.code[self.pc] as usize + 1;
+        self.pc += 1;
+        self.opcode_swap(n);
+    }
 
-        self.stack_push(contract_address);
+    fn opcode_exchange(&mut self) {
+        let (n, m): (usize, usize) = eof::exchange_operands(self.ctx.code[self.pc]);
+        self.pc += 1;
+        let length: usize = self.stack.len();
+        self.stack.swap(length - 1 - n, length - 1 - n - m);
     }
 
-    fn opcode_call(&mut self) {
-        let gas: U256 = self.stack_pop();
-        let address: U256 = self.stack_pop();
+    fn opcode_create(&mut self) {
         let value: U256 = self.stack_pop();
-        let args_offset: usize = self.stack_pop().as_usize();
-        let args_size: usize = self.stack_pop().as_usize();
-        let ret_offset: usize = self.stack_pop().as_usize();
-        let ret_size: usize = self.stack_pop().as_usize();
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
 
-        let account: &blockchain::Account = self.blockchain.get_account(address);
-        let calldata = self.memory[args_offset..args_offset + args_size].to_vec();
-        
-        let msg = context::MSG::new(
-            self.ctx.address,
-            address,
-            calldata,
-            value,
-            gas
-        );
+        let init_code: Vec<u8> = match self.load_init_code(offset, size, 0) {
+            Some(init_code) => init_code,
+            None => return
+        };
 
-        let ctx = context::CTX::new(
-            self.ctx.tx,
-            msg,
-            address,
-            account.code.clone()
-        );
+        let nonce: U256 = self.blockchain.get_nonce(self.ctx.address);
+        let contract_address: U256 = utils::create_address(self.ctx.address, nonce);
 
-        let mut vm = EVM::new(self.blockchain, ctx);
+        let result: U256 = self.create(value, init_code, Vec::new(), contract_address);
+        self.stack_push(result);
+    }
 
-        let success: bool;
-        let returndata: Vec<u8>;
-        (success, returndata) = vm.run();
-        if ret_size > 0 {
-            self.memory_store(ret_offset, returndata[0..ret_size].to_vec());
+    // reads the init code of CREATE and CREATE2 out of memory. Since Shanghai
+    // it is limited in size and metered per word (EIP-3860), `word_gas` is
+    // charged per word on top of that
+    fn load_init_code(&mut self, offset: U256, size: U256, word_gas: u64) -> Option<Vec<u8>> {
+        if !self.expand_memory(offset, size) {
+            return None;
+        }
+        let size: usize = size.as_usize();
+        let mut word_gas: u64 = word_gas;
+        if self.blockchain.get_hardfork().is_enabled(Hardfork::Shanghai) {
+            if size > MAX_INITCODE_SIZE {
+                self.success = false;
+                return None;
+            }
+            word_gas += INITCODE_WORD_GAS;
+        }
+        let words: U256 = U256::from(size.div_ceil(32) as u64);
+        if !self.use_gas(words * U256::from(word_gas)) {
+            return None;
+        }
+
+        if size == 0 {
+            return Some(Vec::new());
         }
+        return Some(self.memory_load(offset.as_usize(), size));
+    }
 
+    // EIP-211: the callee's output replaces the return data buffer, memory
+    // only gets as much of it as fits in the space the caller reserved
+    fn finish_call(&mut self, success: bool, returndata: Vec<u8>, ret_offset: U256, ret_size: U256) {
+        if !self.expand_memory(ret_offset, ret_size) {
+            return;
+        }
+        let size: usize = returndata.len().min(ret_size.as_usize());
+        if size > 0 {
+            self.memory_store(ret_offset.as_usize(), returndata[..size].to_vec());
+        }
         self.returndata = returndata;
         self.stack_push(utils::bool_to_u256(success));
     }
 
+    fn opcode_call(&mut self) {
+        self.message_call(CallKind::Call);
+    }
+
     fn opcode_callcode(&mut self) {
+        self.message_call(CallKind::CallCode);
+    }
+
+    // the legacy calls. The callee gets the gas asked for, capped at all but a
+    // 64th of ours since Tangerine Whistle (EIP-150), plus a stipend when value
+    // is sent. A call that cannot be made fails without failing the caller
+    fn message_call(&mut self, kind: CallKind) {
         let gas: U256 = self.stack_pop();
         let address: U256 = self.stack_pop();
-        let value: U256 = self.stack_pop();
-        let args_offset: usize = self.stack_pop().as_usize();
-        let args_size: usize = self.stack_pop().as_usize();
-        let ret_offset: usize = self.stack_pop().as_usize();
-        let ret_size: usize = self.stack_pop().as_usize();
+        let value: U256 = if kind == CallKind::Call || kind == CallKind::CallCode {
+            self.stack_pop()
+        } else {
+            U256::new(0)
+        };
+        let args_offset: U256 = self.stack_pop();
+        let args_size: U256 = self.stack_pop();
+        let ret_offset: U256 = self.stack_pop();
+        let ret_size: U256 = self.stack_pop();
 
-        let account: &blockchain::Account = self.blockchain.get_account(address);
-        let calldata = self.memory[args_offset..args_offset + args_size].to_vec();
+        if !self.expand_memory(args_offset, args_size) || !self.expand_memory(ret_offset, ret_size) {
+            return;
+        }
 
+        // calling an account into existence costs extra, since Spurious Dragon
+        // only when value is sent to an empty one
+        let hardfork: Hardfork = self.blockchain.get_hardfork();
+        if kind == CallKind::Call {
+            let new_account: bool = if hardfork.is_enabled(Hardfork::SpuriousDragon) {
+                value > 0 && self.blockchain.is_empty_account(address)
+            } else {
+                !self.blockchain.has_account(address)
+            };
+            if new_account && !self.use_gas(U256::from(NEW_ACCOUNT_GAS)) {
+                return;
+            }
+        }
+        if value > 0 && !self.use_gas(U256::from(CALL_VALUE_GAS)) {
+            return;
+        }
+
+        let gas: U256 = if hardfork.is_enabled(Hardfork::TangerineWhistle) {
+            gas.min(self.all_but_one_64th())
+        } else {
+            gas
+        };
+        if !self.use_gas(gas) {
+            return;
+        }
+
+        // the stipend is handed back with the gas when the call cannot be
+        // made, so the caller ends up with it
+        let stipend: U256 = if value > 0 { U256::from(CALL_STIPEND) } else { U256::new(0) };
+        if self.blockchain.get_balance(self.ctx.address) < value ||
+           self.depth + 1 > CALL_DEPTH_LIMIT {
+            self.ctx.msg.gas += gas + stipend;
+            self.finish_call(false, Vec::new(), ret_offset, ret_size);
+            return;
+        }
+
+        let calldata: Vec<u8> = if args_size == 0 {
+            Vec::new()
+        } else {
+            self.memory_load(args_offset.as_usize(), args_size.as_usize())
+        };
+
+        // CALLCODE and DELEGATECALL run the code of `address` on our own
+        // account, DELEGATECALL keeps our caller and value without moving it
+        let (from, call_value, transfer): (U256, U256, U256) = match kind {
+            CallKind::DelegateCall => (self.ctx.msg.from, self.ctx.msg.value, U256::new(0)),
+            _ => (self.ctx.address, value, value)
+        };
+        let frame_address: U256 = match kind {
+            CallKind::Call | CallKind::StaticCall => address,
+            CallKind::CallCode | CallKind::DelegateCall => self.ctx.address
+        };
+
+        let code: Vec<u8> = self.blockchain.get_executable_code(address);
         let msg = context::MSG::new(
-            self.ctx.address,
+            from,
             address,
             calldata,
-            value,
-            gas
+            call_value,
+            gas + stipend
         );
-
         let ctx = context::CTX::new(
-            self.ctx.tx,
+            self.ctx.tx.clone(),
             msg,
-            self.ctx.address,
-            account.code.clone()
+            frame_address,
+            code
         );
 
-        let mut vm = EVM::new(self.blockchain, ctx);
-        let returndata: Vec<u8>;
-        let success: bool;
-        (success, returndata) = vm.run();
-        if ret_size > 0 {
-            self.memory_store(ret_offset, returndata[0..ret_size].to_vec());
-        }
-
-        self.returndata = returndata;
-        self.stack_push(utils::bool_to_u256(success));
+        let result: FrameResult = execute_call(self.blockchain, ctx, self.depth + 1, transfer);
+        self.ctx.msg.gas += result.gas_left;
+        self.finish_call(result.success, result.output, ret_offset, ret_size);
     }
 
     fn opcode_return(&mut self) -> Vec<u8> {
@@ -969,71 +1308,52 @@ impl <'a>EVM<'a> {
     }
 
     fn opcode_delegatecall(&mut self) {
-        let gas: U256 = self.stack_pop();
-        let address: U256 = self.stack_pop();
-        let value: U256 = self.stack_pop();
-        let args_offset: usize = self.stack_pop().as_usize();
-        let args_size: usize = self.stack_pop().as_usize();
-        let ret_offset: usize = self.stack_pop().as_usize();
-        let ret_size: usize = self.stack_pop().as_usize();
-
-        let account: &blockchain::Account = self.blockchain.get_account(address);
-        let calldata = self.memory[args_offset..args_offset + args_size].to_vec();
-
-        let msg = context::MSG::new(
-            self.ctx.address,
-            address,
-            calldata,
-            value,
-            gas
-        );
-
-        let ctx = context::CTX::new(
-            self.ctx.tx,
-            msg,
-            self.ctx.address,
-            account.code.clone()
-        );
-
-        let mut vm = EVM::new(self.blockchain, ctx);
-
-        let returndata: Vec<u8>;
-        let success: bool;
-        (success, returndata) = vm.run();
-        if ret_size > 0 {
-            self.memory_store(ret_offset, returndata[0..ret_size].to_vec());
-        }
-
-        self.returndata = returndata;
-        self.stack_push(utils::bool_to_u256(success));
+        self.message_call(CallKind::DelegateCall);
     }
 
     fn opcode_create2(&mut self) {
         let value: U256 = self.stack_pop();
-        let offset: usize = self.stack_pop().as_usize();
-        let size: usize = self.stack_pop().as_usize();
+        let offset: U256 = self.stack_pop();
+        let size: U256 = self.stack_pop();
         let salt: U256 = self.stack_pop();
 
+        // the init code is hashed for the address
+        let init_code: Vec<u8> = match self.load_init_code(offset, size, KECCAK_WORD_GAS) {
+            Some(init_code) => init_code,
+            None => return
+        };
+        let contract_address: U256 = utils::create2_address(self.ctx.address, salt, &init_code);
 
-        let mut keccak256 = Keccak256::new();
-        // keccak256(0xff + sender_address + salt + keccak256(initialisation_code))[12:]
-        let sender_address = &self.ctx.address.to_be_bytes()[12..32];
-        let init_code: Vec<u8> = self.memory_load(offset, size);
-        keccak256.update(init_code.clone());
-        let hashed_init_code: U256 = U256::from_be_bytes(keccak256.finalize().into());
-
-        let mut data: Vec<u8> = Vec::new();
-        data.push(0xff);
-        data.extend(sender_address);
-        data.extend(salt.to_be_bytes());
-        data.extend(hashed_init_code.to_be_bytes());
-
-        keccak256 = Keccak256::new();
-        keccak256.update(data);
-        let result = hex::encode(&keccak256.finalize()[12..32]);
-        let mut contract_address: U256 = U256::from_str_radix(&result, 16).unwrap();
-        let calldata: Vec<u8> = Vec::new();
-        let gas = U256::new(10000);
+        let result: U256 = self.create(value, init_code, Vec::new(), contract_address);
+        self.stack_push(result);
+    }
+
+    // shared tail of CREATE and CREATE2, returns the new address or zero on
+    // failure. The init code gets all our gas, all but a 64th of it since
+    // Tangerine Whistle (EIP-150)
+    fn create(&mut self, value: U256, init_code: Vec<u8>, calldata: Vec<u8>, contract_address: U256) -> U256 {
+        let gas: U256 = if self.blockchain.get_hardfork().is_enabled(Hardfork::TangerineWhistle) {
+            self.all_but_one_64th()
+        } else {
+            self.ctx.msg.gas
+        };
+        self.ctx.msg.gas -= gas;
+        self.returndata = Vec::new();
+
+        // failed preconditions hand the gas back and leave the nonce untouched
+        if self.blockchain.get_balance(self.ctx.address) < value ||
+           self.blockchain.get_nonce(self.ctx.address) >= U256::from(u64::MAX) ||
+           self.depth + 1 > CALL_DEPTH_LIMIT {
+            self.ctx.msg.gas += gas;
+            return U256::new(0);
+        }
+        self.blockchain.get_account(self.ctx.address).nonce += 1;
+
+        // EOF init code only runs through EOFCREATE, to CREATE and CREATE2 the
+        // leading 0xEF is an undefined instruction
+        if self.eof.is_none() && init_code.first() == Some(&0xEF) {
+            return U256::new(0);
+        }
 
         let msg = context::MSG::new(
             self.ctx.address,
@@ -1044,66 +1364,140 @@ impl <'a>EVM<'a> {
         );
 
         let ctx = context::CTX::new(
-            self.ctx.tx,
+            self.ctx.tx.clone(),
             msg,
             contract_address,
             init_code
         );
 
-        let mut vm = EVM::new(self.blockchain, ctx);
+        let result: FrameResult = execute_create(self.blockchain, ctx, self.depth + 1);
+        self.ctx.msg.gas += result.gas_left;
+        if !result.success {
+            if result.reverted {
+                self.returndata = result.output;
+            }
+            return U256::new(0);
+        }
+        return contract_address;
+    }
 
-        let success: bool;
-        let returndata: Vec<u8>;
-        (success, returndata) = vm.run();
+    // EIP-7620: deploys one of the subcontainers, the address only depends on
+    // the creator, the salt and the init container
+    fn opcode_eofcreate(&mut self) {
+        let index: usize = self.ctx.code[self.pc] as usize;
+        self.pc += 1;
+        let value: U256 = self.stack_pop();
+        let salt: U256 = self.stack_pop();
+        let input_offset: U256 = self.stack_pop();
+        let input_size: U256 = self.stack_pop();
 
-        if success {
-            self.blockchain.add_contract_bin(contract_address, returndata);
-        } else {
-            contract_address = U256::new(0);
+        let input: Vec<u8> = match self.memory_read(input_offset, input_size) {
+            Some(input) => input,
+            None => return
+        };
+        // the init container is hashed for the address
+        let init_code: Vec<u8> = self.container().container_sections[index].clone();
+        let words: U256 = U256::from(init_code.len().div_ceil(32) as u64);
+        if !self.use_gas(words * U256::from(KECCAK_WORD_GAS)) {
+            return;
         }
+        let contract_address: U256 = utils::create2_address(self.ctx.address, salt, &init_code);
 
-        self.stack_push(contract_address);
+        let result: U256 = self.create(value, init_code, input, contract_address);
+        self.stack_push(result);
     }
 
-    fn opcode_staticcall(&mut self) {
-        let gas: U256 = self.stack_pop();
+    // ends the init code, handing back a subcontainer with the aux data
+    // appended to its data section
+    fn opcode_returncontract(&mut self) -> Vec<u8> {
+        let index: usize = self.ctx.code[self.pc] as usize;
+        self.pc += 1;
+        let aux_offset: U256 = self.stack_pop();
+        let aux_size: U256 = self.stack_pop();
+
+        let aux_data: Vec<u8> = match self.memory_read(aux_offset, aux_size) {
+            Some(aux_data) => aux_data,
+            None => return Vec::new()
+        };
+        let mut container: eof::Container = match eof::Container::decode(&self.container().container_sections[index]) {
+            Ok(container) => container,
+            Err(_) => {
+                self.success = false;
+                return Vec::new();
+            }
+        };
+        container.data.extend(aux_data);
+        if container.data.len() < container.data_size || container.data.len() > u16::MAX as usize {
+            self.success = false;
+            return Vec::new();
+        }
+        container.data_size = container.data.len();
+        return container.encode();
+    }
+
+    // EIP-7069: the callee gets what is left after keeping back a 64th, and
+    // the outcome is pushed as 0 success, 1 revert or light failure, 2 failure
+    fn opcode_extcall(&mut self) {
         let address: U256 = self.stack_pop();
+        let input_offset: U256 = self.stack_pop();
+        let input_size: U256 = self.stack_pop();
         let value: U256 = self.stack_pop();
-        let args_offset: usize = self.stack_pop().as_usize();
-        let args_size: usize = self.stack_pop().as_usize();
-        let ret_offset: usize = self.stack_pop().as_usize();
-        let ret_size: usize = self.stack_pop().as_usize();
 
-        let account: &blockchain::Account = self.blockchain.get_account(address);
-        let calldata = self.memory[args_offset..args_offset + args_size].to_vec();
+        if address & !utils::ADDRESS_MASK != 0 {
+            self.success = false;
+            return;
+        }
+        let input: Vec<u8> = match self.memory_read(input_offset, input_size) {
+            Some(input) => input,
+            None => return
+        };
+        if value > 0 {
+            let mut cost: u64 = CALL_VALUE_GAS;
+            if self.blockchain.is_empty_account(address) {
+                cost += NEW_ACCOUNT_GAS;
+            }
+            if !self.use_gas(U256::from(cost)) {
+                return;
+            }
+        }
 
+        self.returndata = Vec::new();
+        let retained: U256 = (self.ctx.msg.gas / 64).max(U256::from(MIN_RETAINED_GAS));
+        if self.ctx.msg.gas < retained + U256::from(MIN_CALLEE_GAS) ||
+           self.blockchain.get_balance(self.ctx.address) < value ||
+           self.depth + 1 > CALL_DEPTH_LIMIT {
+            self.stack_push(U256::new(1));
+            return;
+        }
+        let gas: U256 = self.ctx.msg.gas - retained;
+        self.ctx.msg.gas -= gas;
+
+        let code: Vec<u8> = self.blockchain.get_executable_code(address);
         let msg = context::MSG::new(
             self.ctx.address,
             address,
-            calldata,
+            input,
             value,
             gas
         );
-
         let ctx = context::CTX::new(
-            self.ctx.tx,
+            self.ctx.tx.clone(),
             msg,
             address,
-            account.code.clone()
+            code
         );
 
-        let mut vm = EVM::new(self.blockchain, ctx);
-        let returndata = vm.run();
-
-        let returndata: Vec<u8>;
-        let success: bool;
-        (success, returndata) = vm.run();
-        if ret_size > 0 {
-            self.memory_store(ret_offset, returndata[0..ret_size].to_vec());
+        let result: FrameResult = execute_call(self.blockchain, ctx, self.depth + 1, value);
+        self.ctx.msg.gas += result.gas_left;
+        let status: u64 = if result.success { 0 } else if result.reverted { 1 } else { 2 };
+        if result.success || result.reverted {
+            self.returndata = result.output;
         }
+        self.stack_push(U256::from(status));
+    }
 
-        self.returndata = returndata;
-        self.stack_push(utils::bool_to_u256(success));
+    fn opcode_staticcall(&mut self) {
+        self.message_call(CallKind::StaticCall);
     }
 
     fn opcode_revert(&mut self) -> Vec<u8> {
@@ -1124,8 +1518,539 @@ impl <'a>EVM<'a> {
     }
 
     fn opcode_selfdestruct(&mut self) {
-        let address: U256 = self.stack_pop();
+        let beneficiary: U256 = self.stack_pop();
+
+        // funding an account that does not exist yet costs extra, since
+        // Spurious Dragon only when a balance actually moves into an empty one
+        let balance: U256 = self.blockchain.get_balance(self.ctx.address);
+        let hardfork: Hardfork = self.blockchain.get_hardfork();
+        let new_account: bool = if hardfork.is_enabled(Hardfork::SpuriousDragon) {
+            balance > 0 && self.blockchain.is_empty_account(beneficiary)
+        } else {
+            !self.blockchain.has_account(beneficiary)
+        };
+        if new_account &&
+           !self.use_gas(U256::from(NEW_ACCOUNT_GAS)) {
+            return;
+        }
+
+        self.blockchain.selfdestruct(self.ctx.address, beneficiary);
+    }
+}
+
+
+// yellow paper memory fee, linear with a quadratic term for large memories
+fn memory_cost(words: usize) -> U256 {
+    let words: U256 = U256::from(words as u64);
+    return words * U256::from(MEMORY_WORD_GAS) + words * words / 512;
+}
+
+// `size` bytes of `data` from `offset`, zero padded past its end
+fn padded_slice(data: &[u8], offset: U256, size: usize) -> Vec<u8> {
+    let mut slice: Vec<u8> = vec![0; size];
+    if offset < U256::from(data.len() as u64) {
+        let start: usize = offset.as_usize();
+        let end: usize = data.len().min(start + size);
+        slice[..end - start].copy_from_slice(&data[start..end]);
+    }
+    return slice;
+}
+
+
+// message call into `ctx.address` running `ctx.code`, `value` moves from the
+// caller to it first. The value transfer and everything the callee does is
+// rolled back when it fails
+pub fn execute_call(blockchain: &mut blockchain::BlockChain, ctx: context::CTX, depth: usize, value: U256) -> FrameResult {
+    let snapshot: blockchain::Snapshot = blockchain.snapshot();
+    blockchain.touch(ctx.address);
+    // CALLCODE and DELEGATECALL also touch the account whose code they run,
+    // one that does not exist is left alone rather than brought into existence
+    if ctx.msg.to != ctx.address && blockchain.has_account(ctx.msg.to) {
+        blockchain.touch(ctx.msg.to);
+    }
+    if !blockchain.transfer(ctx.msg.from, ctx.address, value) {
+        blockchain.revert(snapshot);
+        return FrameResult {
+            success: false,
+            reverted: true,
+            output: Vec::new(),
+            gas_left: ctx.msg.gas
+        };
+    }
+
+    let mut vm = EVM::with_depth(blockchain, ctx, depth);
+    let result: FrameResult = vm.execute();
+    if !result.success {
+        blockchain.revert(snapshot);
+    }
+    return result;
+}
+
+// runs the init code of a new contract at `ctx.address` and deposits the code
+// it returns. Paying for the creation and bumping the creator's nonce is up to the caller
+pub fn execute_create(blockchain: &mut blockchain::BlockChain, ctx: context::CTX, depth: usize) -> FrameResult {
+    let contract_address: U256 = ctx.address;
+
+    // address collision, the gas given to the init code is lost
+    if blockchain.has_account(contract_address) {
+        let account: &blockchain::Account = blockchain.get_account(contract_address);
+        if account.nonce != 0 || !account.code.is_empty() || !account.storage.is_empty() {
+            return FrameResult {
+                success: false,
+                reverted: false,
+                output: Vec::new(),
+                gas_left: U256::new(0)
+            };
+        }
+    }
+
+    // EOF init code hands back the container to deploy through RETURNCONTRACT.
+    // Init code that does not validate fails like an exceptional halt
+    let eof_init: bool = blockchain.is_eof_enabled() && eof::is_eof(&ctx.code);
+    let mut init_container: Option<eof::Container> = None;
+    if eof_init {
+        match eof::Container::validated(&ctx.code, eof::ContainerKind::Initcode) {
+            Ok(container) => init_container = Some(container),
+            Err(_) => return FrameResult {
+                success: false,
+                reverted: false,
+                output: Vec::new(),
+                gas_left: U256::new(0)
+            }
+        }
+    }
+
+    let snapshot: blockchain::Snapshot = blockchain.snapshot();
+    let hardfork: Hardfork = blockchain.get_hardfork();
+    blockchain.create_account(contract_address);
+    // EIP-161: contracts start with nonce 1
+    if hardfork.is_enabled(Hardfork::SpuriousDragon) {
+        blockchain.get_account(contract_address).nonce = U256::new(1);
+    }
+    blockchain.mark_created(contract_address);
+    blockchain.transfer(ctx.msg.from, contract_address, ctx.msg.value);
+
+    let mut vm = EVM::with_depth(blockchain, ctx, depth);
+    vm.eof = init_container;
+    let result: FrameResult = vm.execute();
+    if !result.success {
+        blockchain.revert(snapshot);
+        return result;
+    }
+
+    // deployed code checks: EIP-170 size limit since Spurious Dragon, EIP-3541
+    // 0xEF prefix since London unless it is a valid EOF container, deposit gas
+    let code: Vec<u8> = result.output;
+    let deposit_gas: U256 = U256::from(code.len() as u64) * U256::from(CODE_DEPOSIT_GAS);
+    let invalid_prefix: bool = if eof_init {
+        eof::Container::validated(&code, eof::ContainerKind::Runtime).is_err()
+    } else {
+        hardfork.is_enabled(Hardfork::London) && code.first() == Some(&0xEF)
+    };
+    if (hardfork.is_enabled(Hardfork::SpuriousDragon) && code.len() > MAX_CODE_SIZE) ||
+       invalid_prefix ||
+       result.gas_left < deposit_gas {
+        blockchain.revert(snapshot);
+        return FrameResult {
+            success: false,
+            reverted: false,
+            output: Vec::new(),
+            gas_left: U256::new(0)
+        };
+    }
+
+    blockchain.get_account(contract_address).code = code;
+    return FrameResult {
+        success: true,
+        reverted: false,
+        output: Vec::new(),
+        gas_left: result.gas_left - deposit_gas
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FACTORY: U256 = U256::from_words(0, 0x4242);
+    const CALLER: U256 = U256::from_words(0, 0x1000);
+
+    // init code deploying the single byte STOP
+    const DEPLOY_STOP: &str = "600060005360016000f3";
+
+    // runs CREATE (or CREATE2 with `salt`) twice over `init`, storing the
+    // addresses in slots 0 and 1. Sticks to opcodes every fork has
+    fn creating(init: &str, salt: Option<u8>) -> Vec<u8> {
+        let init: Vec<u8> = hex::decode(init).unwrap();
+        let size: u8 = init.len() as u8;
+        let mut code: Vec<u8> = vec![0x5F + size];
+        code.extend(&init);
+        code.extend([0x60, 0x00, 0x52]);
+        for slot in 0..2 {
+            if let Some(salt) = salt {
+                code.extend([0x60, salt]);
+            }
+            code.extend([0x60, size, 0x60, 32 - size, 0x60, 0x00]);
+            code.push(if salt.is_some() { 0xF5 } else { 0xF0 });
+            code.extend([0x60, slot, 0x55]);
+        }
+        code.push(0x00);
+        return code;
+    }
+
+    // installs `code` at FACTORY and calls it with 10M gas
+    fn call(chain: &mut blockchain::BlockChain, code: Vec<u8>) -> FrameResult {
+        return call_with_data(chain, code, Vec::new());
+    }
+
+    fn call_with_data(chain: &mut blockchain::BlockChain, code: Vec<u8>, calldata: Vec<u8>) -> FrameResult {
+        chain.add_contract_bin(FACTORY, code.clone());
+        // as if the factory had been deployed itself
+        chain.set_nonce(FACTORY, U256::new(1));
+        let tx = context::TX::new(CALLER, U256::new(0));
+        let msg = context::MSG::new(CALLER, FACTORY, calldata, U256::new(0), U256::new(10_000_000));
+        return execute_call(chain, context::CTX::new(tx, msg, FACTORY, code), 0, U256::new(0));
+    }
+
+    fn run(hardfork: Hardfork, code: Vec<u8>) -> (blockchain::BlockChain, FrameResult) {
+        let mut chain = blockchain::BlockChain::new();
+        chain.set_hardfork(hardfork);
+        let result: FrameResult = call(&mut chain, code);
+        return (chain, result);
+    }
+
+    // a container with one non-returning code section
+    fn eof_container(code: &str, max_stack_increase: u16, containers: Vec<Vec<u8>>) -> Vec<u8> {
+        let container = eof::Container {
+            types: vec![eof::FunctionType {
+                inputs: 0,
+                outputs: eof::NON_RETURNING,
+                max_stack_increase: max_stack_increase
+            }],
+            code_sections: vec![hex::decode(code).unwrap()],
+            container_sections: containers,
+            data: Vec::new(),
+            data_size: 0
+        };
+        return container.encode();
+    }
+
+    fn run_eof(code: Vec<u8>) -> (blockchain::BlockChain, FrameResult) {
+        let mut chain = blockchain::BlockChain::new();
+        chain.set_eof_enabled(true);
+        let result: FrameResult = call(&mut chain, code);
+        return (chain, result);
+    }
+
+    #[test]
+    fn test_create() {
+        let (mut chain, result) = run(Hardfork::Cancun, creating(DEPLOY_STOP, None));
+        assert!(result.success);
+        // every CREATE bumps the creator's nonce and new contracts start at 1
+        let first: U256 = utils::create_address(FACTORY, U256::new(1));
+        let second: U256 = utils::create_address(FACTORY, U256::new(2));
+        assert_eq!(chain.get_storage(FACTORY, U256::new(0)), first);
+        assert_eq!(chain.get_storage(FACTORY, U256::new(1)), second);
+        assert_eq!(chain.get_nonce(FACTORY), U256::new(3));
+        for address in [first, second] {
+            assert_eq!(chain.get_code(address), vec![0x00]);
+            assert_eq!(chain.get_nonce(address), U256::new(1));
+        }
+        assert!(chain.get_account(first).storage.is_empty());
+    }
+
+    #[test]
+    fn test_create2() {
+        let (chain, result) = run(Hardfork::Cancun, creating(DEPLOY_STOP, Some(7)));
+        assert!(result.success);
+        let expected: U256 = utils::create2_address(FACTORY, U256::new(7), &hex::decode(DEPLOY_STOP).unwrap());
+        assert_eq!(chain.get_storage(FACTORY, U256::new(0)), expected);
+        assert_eq!(chain.get_code(expected), vec![0x00]);
+        // the second attempt collides with the first contract
+        assert_eq!(chain.get_storage(FACTORY, U256::new(1)), U256::new(0));
+        assert_eq!(chain.get_nonce(FACTORY), U256::new(3));
+    }
+
+    #[test]
+    fn test_create_nonce_before_spurious_dragon() {
+        let (chain, result) = run(Hardfork::Homestead, creating(DEPLOY_STOP, None));
+        assert!(result.success);
+        let first: U256 = chain.get_storage(FACTORY, U256::new(0));
+        assert_eq!(first, utils::create_address(FACTORY, U256::new(1)));
+        assert_eq!(chain.get_nonce(first), U256::new(0));
+    }
+
+    #[test]
+    fn test_create_gas_before_tangerine_whistle() {
+        // CREATE over the init code 0xFE, which burns whatever it is given
+        let code: Vec<u8> = hex::decode("60fe600053600160006000f000").unwrap();
+        let (_, result) = run(Hardfork::Homestead, code.clone());
+        assert!(result.success);
+        assert_eq!(result.gas_left, U256::new(0));
+
+        let (_, result) = run(Hardfork::TangerineWhistle, code);
+        assert!(result.success);
+        assert!(result.gas_left > U256::new(10_000_000 / 64 - 1000));
+    }
+
+    #[test]
+    fn test_create_deposit_rules() {
+        // EIP-3541: no new code starting with 0xEF since London
+        let deploy_ef: &str = "60ef60005360016000f3";
+        let (chain, _) = run(Hardfork::London, creating(deploy_ef, None));
+        assert_eq!(chain.get_storage(FACTORY, U256::new(0)), U256::new(0));
+        let (chain, _) = run(Hardfork::Berlin, creating(deploy_ef, None));
+        assert_ne!(chain.get_storage(FACTORY, U256::new(0)), U256::new(0));
+
+        // EIP-170: at most 0x6000 bytes of code since Spurious Dragon
+        let (chain, _) = run(Hardfork::Cancun, creating("6160006000f3", None));
+        assert_eq!(chain.get_code(chain.get_storage(FACTORY, U256::new(0))).len(), MAX_CODE_SIZE);
+        let (chain, _) = run(Hardfork::Cancun, creating("6160016000f3", None));
+        assert_eq!(chain.get_storage(FACTORY, U256::new(0)), U256::new(0));
+        let (chain, _) = run(Hardfork::Homestead, creating("6160016000f3", None));
+        assert_eq!(chain.get_code(chain.get_storage(FACTORY, U256::new(0))).len(), MAX_CODE_SIZE + 1);
+    }
+
+    #[test]
+    fn test_create_empty_init_code() {
+        // size 0 never touches memory, whatever the offset
+        let (chain, result) = run(Hardfork::Cancun, hex::decode("60007fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff6000f060005500").unwrap());
+        assert!(result.success);
+        let created: U256 = chain.get_storage(FACTORY, U256::new(0));
+        assert_eq!(created, utils::create_address(FACTORY, U256::new(1)));
+        assert!(chain.has_account(created));
+        assert!(chain.get_code(created).is_empty());
+
+        // reading init code from that far out cannot be paid for
+        let (_, result) = run(Hardfork::Cancun, hex::decode("60017fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff6000f060005500").unwrap());
+        assert!(!result.success);
+    }
+
+    #[test]
+    fn test_invalid_eof_does_not_run() {
+        // a single section holding the undefined opcode 0x0c
+        let code: Vec<u8> = hex::decode("ef00010100040200010001040000000080000000").unwrap();
+        let mut invalid: Vec<u8> = code.clone();
+        *invalid.last_mut().unwrap() = 0x0C;
+
+        // without EOF the container is legacy code starting with the invalid 0xEF
+        let (_, result) = run(Hardfork::Cancun, code.clone());
+        assert!(!result.success);
+        for (code, success) in [(code, true), (invalid, false)] {
+            let (_, result) = run_eof(code);
+            assert_eq!(result.success, success);
+        }
+    }
+
+    // 2^256 - 1, an offset no memory could reach
+    const HUGE: &str = "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+
+    #[test]
+    fn test_eof_memory_operands() {
+        let deploy_stop: Vec<u8> = eof_container("5f5fee00", 2, vec![eof_container("00", 0, Vec::new())]);
+
+        // EXTCALL reading its input from far out of memory
+        let (_, result) = run_eof(eof_container(&format!("5f6001{}61deadf800", HUGE), 4, Vec::new()));
+        assert!(!result.success);
+        assert_eq!(result.gas_left, U256::new(0));
+
+        // EOFCREATE with an input that could never be paid for
+        let (chain, result) = run_eof(eof_container(&format!("{}5f5f5fec0000", HUGE), 4, vec![deploy_stop.clone()]));
+        assert!(!result.success);
+        assert_eq!(chain.get_nonce(FACTORY), U256::new(1));
+
+        // RETURNCONTRACT with huge aux data fails the init code, not the creator
+        let aux: Vec<u8> = eof_container(&format!("6001{}ee00", HUGE), 2, vec![eof_container("00", 0, Vec::new())]);
+        let (chain, result) = run_eof(eof_container("5f5f5f5fec005f5500", 4, vec![aux]));
+        assert!(result.success);
+        assert_eq!(chain.get_nonce(FACTORY), U256::new(2));
+        assert_eq!(chain.get_storage(FACTORY, U256::new(0)), U256::new(0));
+
+        // and the same with sane operands deploys, paying for hashing the init container
+        let (chain, result) = run_eof(eof_container("5f5f5f5fec005f5500", 4, vec![deploy_stop.clone()]));
+        assert!(result.success);
+        let created: U256 = utils::create2_address(FACTORY, U256::new(0), &deploy_stop);
+        assert_eq!(chain.get_storage(FACTORY, U256::new(0)), created);
+        assert_eq!(chain.get_code(created), eof_container("00", 0, Vec::new()));
+    }
+
+    #[test]
+    fn test_reverted_create() {
+        // init code that stores and reverts leaves nothing behind but the nonce
+        let (chain, result) = run(Hardfork::Cancun, creating("600160005560006000fd", None));
+        assert!(result.success);
+        assert_eq!(chain.get_storage(FACTORY, U256::new(0)), U256::new(0));
+        assert!(!chain.has_account(utils::create_address(FACTORY, U256::new(1))));
+        assert_eq!(chain.get_nonce(FACTORY), U256::new(3));
+    }
+
+    const RETURNER: U256 = U256::from_words(0, 0xbeef);
+
+    // calls RETURNER, which returns 32 bytes, then runs `code`
+    fn after_call(code: &[u8]) -> Vec<u8> {
+        let mut calling: Vec<u8> = vec![0x5f, 0x5f, 0x5f, 0x5f, 0x5f, 0x61, 0xbe, 0xef, 0x5a, 0xf1, 0x50];
+        calling.extend(code);
+        return calling;
+    }
+
+    fn push32(value: U256) -> Vec<u8> {
+        let mut code: Vec<u8> = vec![0x7f];
+        code.extend(value.to_be_bytes());
+        return code;
+    }
+
+    #[test]
+    fn test_returndatacopy_bounds() {
+        // copying past the end of the buffer fails the frame, even an empty copy
+        let cases: [(U256, U256, bool); 9] = [
+            (U256::new(0), U256::new(32), true),
+            (U256::new(1), U256::new(31), true),
+            (U256::new(0), U256::new(0), true),
+            (U256::new(32), U256::new(0), true),
+            (U256::new(0), U256::new(33), false),
+            (U256::new(1), U256::new(32), false),
+            (U256::new(33), U256::new(0), false),
+            (U256::MAX, U256::new(2), false),
+            (U256::new(2), U256::MAX, false),
+        ];
+        for (offset, size, success) in cases {
+            let mut code: Vec<u8> = push32(size);
+            code.extend(push32(offset));
+            code.extend([0x5f, 0x3e, 0x00]);
+            let mut chain = blockchain::BlockChain::new();
+            chain.add_contract_bin(RETURNER, hex::decode("60aa5f5260205ff3").unwrap());
+            let result: FrameResult = call(&mut chain, after_call(&code));
+            assert_eq!(result.success, success, "offset {} size {}", offset, size);
+            if !success {
+                assert_eq!(result.gas_left, U256::new(0));
+            }
+        }
+
+        // before any call the buffer is empty
+        let (_, result) = run(Hardfork::Cancun, vec![0x5f, 0x5f, 0x5f, 0x3e, 0x00]);
+        assert!(result.success);
+        let (_, result) = run(Hardfork::Cancun, vec![0x60, 0x01, 0x5f, 0x5f, 0x3e, 0x00]);
+        assert!(!result.success);
+    }
+
+    #[test]
+    fn test_returndata_replaced() {
+        // RETURNDATASIZE after `code`, which follows a call returning 32 bytes
+        let init: Vec<u8> = hex::decode(DEPLOY_STOP).unwrap();
+        let mut create: Vec<u8> = vec![0x69];
+        create.extend(&init);
+        create.extend([0x5f, 0x52, 0x60, 0x0a, 0x60, 0x16, 0x5f, 0xf0, 0x50]);
+        let mut reverting_create: Vec<u8> = vec![0x67];
+        reverting_create.extend(hex::decode("60aa5f5260205ffd").unwrap());
+        reverting_create.extend([0x5f, 0x52, 0x60, 0x08, 0x60, 0x18, 0x5f, 0xf0, 0x50]);
+
+        let cases: [(Vec<u8>, u64); 5] = [
+            (Vec::new(), 32),
+            // a successful CREATE leaves the buffer empty, a reverted one its revert data
+            (create, 0),
+            (reverting_create, 32),
+            // so do a CALL and a CREATE sending more than the balance
+            (vec![0x5f, 0x5f, 0x5f, 0x5f, 0x60, 0x01, 0x61, 0xbe, 0xef, 0x5a, 0xf1, 0x50], 0),
+            (vec![0x5f, 0x5f, 0x60, 0x01, 0xf0, 0x50], 0),
+        ];
+        for (i, (code, size)) in cases.iter().enumerate() {
+            let mut code: Vec<u8> = after_call(code);
+            code.extend([0x3d, 0x5f, 0x55, 0x00]);
+            let mut chain = blockchain::BlockChain::new();
+            chain.add_contract_bin(RETURNER, hex::decode("60aa5f5260205ff3").unwrap());
+            let result: FrameResult = call(&mut chain, code);
+            assert!(result.success, "case {}", i);
+            assert_eq!(chain.get_storage(FACTORY, U256::new(0)), U256::from(*size), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn test_copies_from_huge_offsets() {
+        // fills the first memory word with ones, runs the copy (or CALLDATALOAD
+        // into that word) and stores the word in slot 0. Calldata is 40 bytes
+        // of 0x11 and RETURNER's code ends in 0xf3
+        let run_copy = |opcode: u8, dest: U256, offset: U256, size: U256| -> (bool, U256) {
+            let mut code: Vec<u8> = push32(U256::MAX);
+            code.extend([0x5f, 0x52]);
+            if opcode == 0x35 {
+                code.extend(push32(offset));
+                code.extend([0x35, 0x5f, 0x52]);
+            } else {
+                code.extend(push32(size));
+                code.extend(push32(offset));
+                code.extend(push32(dest));
+                if opcode == 0x3c {
+                    code.extend([0x61, 0xbe, 0xef]);
+                }
+                code.push(opcode);
+            }
+            code.extend([0x5f, 0x51, 0x5f, 0x55, 0x00]);
+            let mut chain = blockchain::BlockChain::new();
+            chain.add_contract_bin(RETURNER, hex::decode("60aa5f5260205ff3").unwrap());
+            let result: FrameResult = call_with_data(&mut chain, code, vec![0x11; 40]);
+            return (result.success, chain.get_storage(FACTORY, U256::new(0)));
+        };
+
+        let zero: U256 = U256::new(0);
+        let word: U256 = U256::new(32);
+        let huge: [U256; 5] = [
+            U256::from(usize::MAX as u64) - 1,
+            U256::from(usize::MAX as u64),
+            U256::from(usize::MAX as u64) + 1,
+            U256::from(u64::MAX) * 2,
+            U256::MAX,
+        ];
+        for opcode in [0x35, 0x37, 0x39, 0x3c] {
+            // past the end the source reads as zeros
+            for offset in huge {
+                assert_eq!(run_copy(opcode, zero, offset, word), (true, zero), "opcode {:#x} offset {}", opcode, offset);
+            }
+            // asking for that many bytes, or writing them that far, runs out of gas
+            if opcode != 0x35 {
+                for value in huge {
+                    assert!(!run_copy(opcode, zero, zero, value).0, "opcode {:#x} size {}", opcode, value);
+                    assert!(!run_copy(opcode, value, zero, word).0, "opcode {:#x} dest {}", opcode, value);
+                }
+                // an empty copy costs nothing whatever the operands
+                assert_eq!(run_copy(opcode, U256::MAX, U256::MAX, zero), (true, U256::MAX), "opcode {:#x}", opcode);
+            }
+        }
+
+        // the last byte, then padding
+        assert_eq!(run_copy(0x35, zero, U256::new(39), word), (true, U256::new(0x11) << 248));
+        assert_eq!(run_copy(0x37, zero, U256::new(39), word), (true, U256::new(0x11) << 248));
+        assert_eq!(run_copy(0x3c, zero, U256::new(7), word), (true, U256::new(0xf3) << 248));
+    }
+
+    #[test]
+    fn test_failed_call_keeps_stipend() {
+        // gas spent from GAS to GAS around a CALL sending `value` to an empty
+        // account with 10000 gas, and the call's result
+        let spent = |value: u8, balance: u64| -> (U256, U256) {
+            let code: Vec<u8> = vec![
+                0x5a, 0x5f, 0x5f, 0x5f, 0x5f, 0x60, value, 0x61, 0xbe, 0xef, 0x61, 0x27, 0x10, 0xf1,
+                0x90, 0x5a, 0x90, 0x03, 0x5f, 0x55, 0x60, 0x01, 0x55, 0x00,
+            ];
+            let mut chain = blockchain::BlockChain::new();
+            let tx = context::TX::new(CALLER, U256::new(0));
+            let msg = context::MSG::new(CALLER, FACTORY, Vec::new(), U256::new(0), U256::new(10_000_000));
+            chain.add_contract_bin(FACTORY, code.clone());
+            chain.fund(FACTORY, U256::from(balance));
+            let result: FrameResult = execute_call(&mut chain, context::CTX::new(tx, msg, FACTORY, code), 0, U256::new(0));
+            assert!(result.success);
+            return (chain.get_storage(FACTORY, U256::new(0)), chain.get_storage(FACTORY, U256::new(1)));
+        };
 
-        self.blockchain.register_to_destroy(self.ctx.address, address);
+        let (without_value, result) = spent(0, 0);
+        assert_eq!(result, U256::new(1));
+        // the callee stops at once, so its gas and the stipend come back
+        let (made, result) = spent(1, 1);
+        assert_eq!(result, U256::new(1));
+        assert_eq!(made, without_value + U256::from(CALL_VALUE_GAS + NEW_ACCOUNT_GAS - CALL_STIPEND));
+        // without the balance to send nothing runs, and the caller is left with the stipend all the same
+        let (failed, result) = spent(1, 0);
+        assert_eq!(result, U256::new(0));
+        assert_eq!(failed, made);
     }
 }
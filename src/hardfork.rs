@@ -0,0 +1,60 @@
+#![allow(clippy::needless_return)]
+
+// mainnet forks in activation order, so rules can be gated with comparisons
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hardfork {
+    Frontier,
+    Homestead,
+    TangerineWhistle,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Paris,
+    Shanghai,
+    Cancun,
+    Prague,
+}
+
+impl Hardfork {
+    pub fn is_enabled(&self, fork: Hardfork) -> bool {
+        return *self >= fork;
+    }
+
+    // wei minted for the miner of every block, cut by EIP-649 and EIP-1234
+    // and gone with the merge
+    pub fn block_reward(&self) -> u64 {
+        if self.is_enabled(Hardfork::Paris) {
+            return 0;
+        }
+        if self.is_enabled(Hardfork::Constantinople) {
+            return 2_000_000_000_000_000_000;
+        }
+        if self.is_enabled(Hardfork::Byzantium) {
+            return 3_000_000_000_000_000_000;
+        }
+        return 5_000_000_000_000_000_000;
+    }
+
+    // EIP-4844 blob schedule, raised in Prague by EIP-7691
+    pub fn target_blobs_per_block(&self) -> u64 {
+        return if self.is_enabled(Hardfork::Prague) { 6 } else { 3 };
+    }
+
+    pub fn max_blobs_per_block(&self) -> u64 {
+        return if self.is_enabled(Hardfork::Prague) { 9 } else { 6 };
+    }
+
+    pub fn blob_base_fee_update_fraction(&self) -> u64 {
+        return if self.is_enabled(Hardfork::Prague) { 5007716 } else { 3338477 };
+    }
+}
+
+impl Default for Hardfork {
+    fn default() -> Hardfork {
+        return Hardfork::Cancun;
+    }
+}
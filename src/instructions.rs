@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 use std::collections::HashMap;
 use lazy_static::lazy_static;
 
@@ -9,7 +11,7 @@ pub struct Instruction<'a> {
 }
 
 impl Instruction<'_> {
-    fn new(name: &str, gas_cost: u32, operands: usize) -> Instruction {
+    fn new(name: &str, gas_cost: u32, operands: usize) -> Instruction<'_> {
         return Instruction {
             name: name,
             gas_cost: gas_cost,
@@ -68,11 +70,13 @@ lazy_static! {
         instructions.insert(0x41, Instruction::new("COINBASE", 2, 0));
         instructions.insert(0x42, Instruction::new("TIMESTAMP", 2, 0));
         instructions.insert(0x43, Instruction::new("NUMBER", 2, 0));
-        instructions.insert(0x44, Instruction::new("DIFFICULTY", 2, 0));
+        instructions.insert(0x44, Instruction::new("PREVRANDAO", 2, 0));
         instructions.insert(0x45, Instruction::new("GASLIMIT", 2, 0));
         instructions.insert(0x46, Instruction::new("CHAINID", 2, 0));
         instructions.insert(0x47, Instruction::new("SELFBALANCE", 5, 0));
         instructions.insert(0x48, Instruction::new("BASEFEE", 2, 0));
+        instructions.insert(0x49, Instruction::new("BLOBHASH", 3, 0));
+        instructions.insert(0x4A, Instruction::new("BLOBBASEFEE", 2, 0));
         instructions.insert(0x50, Instruction::new("POP", 2, 0));
         instructions.insert(0x51, Instruction::new("MLOAD", 3, 0));
         instructions.insert(0x52, Instruction::new("MSTORE", 3, 0));
@@ -155,12 +159,28 @@ lazy_static! {
         instructions.insert(0xA2, Instruction::new("LOG2", 1125, 0));
         instructions.insert(0xA3, Instruction::new("LOG3", 1500, 0));
         instructions.insert(0xA4, Instruction::new("LOG4", 1875, 0));
+        instructions.insert(0xD0, Instruction::new("DATALOAD", 4, 0));
+        instructions.insert(0xD1, Instruction::new("DATALOADN", 3, 2));
+        instructions.insert(0xD2, Instruction::new("DATASIZE", 2, 0));
+        instructions.insert(0xD3, Instruction::new("DATACOPY", 3, 0));
+        instructions.insert(0xE0, Instruction::new("RJUMP", 2, 2));
+        instructions.insert(0xE1, Instruction::new("RJUMPI", 4, 2));
+        instructions.insert(0xE2, Instruction::new("RJUMPV", 4, 1));
+        instructions.insert(0xE3, Instruction::new("CALLF", 5, 2));
+        instructions.insert(0xE4, Instruction::new("RETF", 3, 0));
+        instructions.insert(0xE5, Instruction::new("JUMPF", 5, 2));
+        instructions.insert(0xE6, Instruction::new("DUPN", 3, 1));
+        instructions.insert(0xE7, Instruction::new("SWAPN", 3, 1));
+        instructions.insert(0xE8, Instruction::new("EXCHANGE", 3, 1));
+        instructions.insert(0xEC, Instruction::new("EOFCREATE", 32000, 1));
+        instructions.insert(0xEE, Instruction::new("RETURNCONTRACT", 0, 1));
         instructions.insert(0xF0, Instruction::new("CREATE", 32000, 0));
         instructions.insert(0xF1, Instruction::new("CALL", 100, 0));
         instructions.insert(0xF2, Instruction::new("CALLCODE", 100, 0));
         instructions.insert(0xF3, Instruction::new("RETURN", 0, 0));
         instructions.insert(0xF4, Instruction::new("DELEGAATECALL", 100, 0));
         instructions.insert(0xF5, Instruction::new("CREATE2", 32000, 0));
+        instructions.insert(0xF8, Instruction::new("EXTCALL", 100, 0));
         instructions.insert(0xFA, Instruction::new("STATICCALL", 100, 0));
         instructions.insert(0xFD, Instruction::new("REVERT", 0, 0));
         instructions.insert(0xFE, Instruction::new("INVALID", 0, 0));
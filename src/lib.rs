@@ -0,0 +1,16 @@
+pub mod arithmetic;
+pub mod context;
+pub mod evm;
+pub mod blockchain;
+pub mod bloom;
+pub mod eof;
+pub mod hardfork;
+pub mod utils;
+pub mod instructions;
+pub mod receipt;
+pub mod requests;
+pub mod proof;
+pub mod rlp;
+pub mod signature;
+pub mod transaction;
+pub mod trie;
@@ -1,14 +1,7 @@
-use std::io::{self,Read};
-use hex;
 use ethnum::{U256};
 use colored::Colorize;
 
-
-mod context;
-mod evm;
-mod blockchain;
-mod utils;
-mod instructions;
+use evm_rust::{blockchain, transaction};
 
 
 fn main() {
@@ -32,39 +25,44 @@ fn main() {
     let inp = "38349011600857FD5B3661000390061534600A0157FDFDFDFD5B00"; // evm puzzles 10
     let bytecode: Vec<u8> = hex::decode(inp).unwrap();
 
-    let alice = "0x41414141";
+    // well known development key, the sender is recovered from its signature
+    let alice_key: U256 = U256::from_str_hex("0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80").unwrap();
     let contract_address = "0x42424242";
 
     let mut blockchain = blockchain::BlockChain::new();
     blockchain.add_contract(contract_address, bytecode.clone());
 
-    let origin: U256 = U256::from_str_hex(alice).unwrap();
-    let gasprice: U256 = U256::new(1000);
-    let from: U256 = U256::from_str_hex(alice).unwrap();
     let to: U256 = U256::from_str_hex(contract_address).unwrap();
+    let nonce: U256 = U256::new(0);
+    let gasprice: U256 = U256::new(2_000_000_000);
     let calldata: Vec<u8> = hex::decode("FFFFFF").unwrap();
     let value: U256 = U256::new(15);
     let gas: U256 = U256::new(10000000);
-    let address: U256 = U256::from_str_hex(contract_address).unwrap();
-
-    let mut tx = context::TX::new(origin, gasprice);
-    let mut msg = context::MSG::new(from, to, calldata, value, gas);
 
-    let mut ctx = context::CTX::new(
-        tx,
-        msg,
-        address,
-        bytecode.clone()
-    );
+    let mut unsigned = transaction::Transaction::new(U256::new(0), Some(to), nonce, gas, gasprice, value, calldata);
+    unsigned.chain_id = Some(blockchain.get_current_block().chainid);
+    unsigned.sign(alice_key).unwrap();
+    let raw: Vec<u8> = unsigned.encode();
 
-    let mut success: bool = false;
-    let mut returndata: Vec<u8>;
-    let mut vm = evm::EVM::new(&mut blockchain, ctx);
-    (success, returndata) = vm.run();
-    vm.print_stack();
-    vm.print_memory();
+    let tx = match transaction::Transaction::decode(&raw) {
+        Ok(tx) => tx,
+        Err(error) => {
+            println!("{} {}", "invalid transaction:".red(), error);
+            return;
+        }
+    };
+    blockchain.fund(tx.from, U256::new(1_000_000_000_000_000_000));
 
-    blockchain.clean_contracts();
+    let result = match blockchain.execute_transaction(&tx) {
+        Ok(result) => result,
+        Err(error) => {
+            println!("{} {}", "invalid transaction:".red(), error);
+            return;
+        }
+    };
+    let success: bool = result.success;
+    let returndata: Vec<u8> = result.output;
+    println!("gas used: {}", result.gas_used);
 
     println!("--- Return Data ---");
     println!(
@@ -0,0 +1,153 @@
+#![allow(clippy::needless_return)]
+
+use ethnum::{U256};
+
+use crate::blockchain::EMPTY_ROOT_HASH;
+use crate::rlp;
+use crate::trie::{self, ProofError};
+use crate::utils;
+
+
+// eth_getProof (EIP-1186) response: the account fields, the state trie path
+// to the account and one storage trie path per requested slot
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountProof {
+    pub address: U256,
+    pub nonce: U256,
+    pub balance: U256,
+    pub storage_hash: U256,
+    pub code_hash: U256,
+    pub account_proof: Vec<Vec<u8>>,
+    pub storage_proof: Vec<StorageProof>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageProof {
+    pub key: U256,
+    pub value: U256,
+    pub proof: Vec<Vec<u8>>
+}
+
+impl AccountProof {
+    // a missing account is reported with empty fields, which is what the
+    // proof has to show when the account is absent from the trie
+    fn is_empty(&self) -> bool {
+        return self.nonce == 0
+            && self.balance == 0
+            && self.storage_hash == EMPTY_ROOT_HASH
+            && self.code_hash == utils::keccak256(&[]);
+    }
+
+    // checks the account against `state_root` and every slot against the
+    // account's storage hash
+    pub fn verify(&self, state_root: U256) -> Result<(), ProofError> {
+        let key: U256 = utils::keccak256(&self.address.to_be_bytes()[12..32]);
+        match trie::verify_proof(state_root, &key.to_be_bytes(), &self.account_proof)? {
+            Some(encoded) => {
+                let expected: Vec<u8> = rlp::encode_list(&[
+                    rlp::encode_u256(self.nonce),
+                    rlp::encode_u256(self.balance),
+                    rlp::encode_hash(self.storage_hash),
+                    rlp::encode_hash(self.code_hash),
+                ]);
+                if encoded != expected {
+                    return Err(ProofError::ValueMismatch);
+                }
+            },
+            None => {
+                if !self.is_empty() {
+                    return Err(ProofError::ValueMismatch);
+                }
+            }
+        }
+
+        for slot in &self.storage_proof {
+            slot.verify(self.storage_hash)?;
+        }
+        return Ok(());
+    }
+}
+
+impl StorageProof {
+    pub fn verify(&self, storage_root: U256) -> Result<(), ProofError> {
+        let key: U256 = utils::keccak256(&self.key.to_be_bytes());
+        let value: U256 = match trie::verify_proof(storage_root, &key.to_be_bytes(), &self.proof)? {
+            Some(encoded) => rlp::decode_as(&encoded)?,
+            None => U256::new(0)
+        };
+        if value != self.value {
+            return Err(ProofError::ValueMismatch);
+        }
+        return Ok(());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::BlockChain;
+
+    fn chain() -> BlockChain {
+        let mut chain: BlockChain = BlockChain::new();
+        for i in 1..=20 {
+            chain.fund(U256::new(i), U256::new(i * 1000));
+        }
+        let contract: U256 = U256::new(0x4242);
+        chain.add_contract_bin(contract, vec![0x00]);
+        chain.set_nonce(contract, U256::new(1));
+        for slot in 0..10 {
+            chain.set_storage(contract, U256::new(slot), U256::new(slot + 100));
+        }
+        return chain;
+    }
+
+    #[test]
+    fn test_account_proofs() {
+        let chain: BlockChain = chain();
+        let root: U256 = chain.state_root();
+
+        let funded: AccountProof = chain.get_proof(U256::new(7), &[]);
+        assert_eq!(funded.balance, U256::new(7000));
+        assert_eq!(funded.verify(root), Ok(()));
+
+        // the proof for a missing account shows it is absent
+        let missing: AccountProof = chain.get_proof(U256::new(0xdead), &[U256::new(1)]);
+        assert_eq!(missing.storage_hash, EMPTY_ROOT_HASH);
+        assert_eq!(missing.verify(root), Ok(()));
+
+        let mut wrong_balance: AccountProof = funded.clone();
+        wrong_balance.balance += 1;
+        assert_eq!(wrong_balance.verify(root), Err(ProofError::ValueMismatch));
+
+        let mut claimed: AccountProof = missing.clone();
+        claimed.nonce = U256::new(1);
+        assert_eq!(claimed.verify(root), Err(ProofError::ValueMismatch));
+
+        assert_eq!(funded.verify(EMPTY_ROOT_HASH), Err(ProofError::HashMismatch));
+    }
+
+    #[test]
+    fn test_storage_proofs() {
+        let chain: BlockChain = chain();
+        let root: U256 = chain.state_root();
+        let slots: Vec<U256> = vec![U256::new(0), U256::new(9), U256::new(10), U256::MAX];
+        let proof: AccountProof = chain.get_proof(U256::new(0x4242), &slots);
+        assert_eq!(proof.verify(root), Ok(()));
+        assert_eq!(proof.storage_proof[1].value, U256::new(109));
+        // unset slots are proven to be zero
+        assert_eq!(proof.storage_proof[2].value, U256::new(0));
+
+        let mut wrong_value: AccountProof = proof.clone();
+        wrong_value.storage_proof[0].value = U256::new(1);
+        assert_eq!(wrong_value.verify(root), Err(ProofError::ValueMismatch));
+
+        let mut claimed: AccountProof = proof.clone();
+        claimed.storage_proof[3].value = U256::new(1);
+        assert_eq!(claimed.verify(root), Err(ProofError::ValueMismatch));
+
+        let mut swapped: AccountProof = proof.clone();
+        swapped.storage_proof[0].proof = proof.storage_proof[1].proof.clone();
+        assert!(swapped.verify(root).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use ethnum::{U256};
+
+use crate::blockchain::Log;
+use crate::bloom::Bloom;
+use crate::rlp::{self, Fields, RlpError, RlpStruct};
+use crate::transaction::TxType;
+
+
+#[derive(Clone, Debug)]
+pub struct Receipt {
+    pub tx_type: TxType,
+    pub status: bool,
+    // before Byzantium receipts commit to the state root after the
+    // transaction instead of its status (EIP-658)
+    pub post_state: Option<U256>,
+    pub cumulative_gas_used: U256,
+    pub logs_bloom: Bloom,
+    pub logs: Vec<Log>
+}
+
+impl Receipt {
+    pub fn new(tx_type: TxType, status: bool, cumulative_gas_used: U256, logs: Vec<Log>) -> Receipt {
+        return Receipt {
+            tx_type: tx_type,
+            status: status,
+            post_state: None,
+            cumulative_gas_used: cumulative_gas_used,
+            logs_bloom: Bloom::from_logs(&logs),
+            logs: logs
+        };
+    }
+
+    // receipts of typed transactions are prefixed with the type like the transaction (EIP-2718)
+    pub fn encode(&self) -> Vec<u8> {
+        if self.tx_type == TxType::Legacy {
+            return rlp::encode(self);
+        }
+        let mut encoded: Vec<u8> = vec![self.tx_type as u8];
+        encoded.extend(rlp::encode(self));
+        return encoded;
+    }
+
+    pub fn decode(raw: &[u8]) -> Result<Receipt, RlpError> {
+        let first: u8 = *raw.first().ok_or(RlpError::UnexpectedEnd)?;
+        if first >= 0xC0 {
+            return rlp::decode_as(raw);
+        }
+
+        let tx_type: TxType = TxType::from_u8(first).ok_or(RlpError::ExpectedList)?;
+        let mut receipt: Receipt = rlp::decode_as(&raw[1..])?;
+        receipt.tx_type = tx_type;
+        return Ok(receipt);
+    }
+}
+
+// [status or post state root, cumulative gas, bloom, logs]
+impl RlpStruct for Receipt {
+    fn encode_fields(&self) -> Vec<Vec<u8>> {
+        let outcome: Vec<u8> = match self.post_state {
+            Some(root) => rlp::encode_hash(root),
+            None => rlp::encode(&self.status)
+        };
+        return vec![
+            outcome,
+            rlp::encode_u256(self.cumulative_gas_used),
+            rlp::encode_bytes(&self.logs_bloom.0),
+            rlp::encode(&self.logs),
+        ];
+    }
+
+    // the status of a pre-Byzantium receipt is not recorded, it decodes as successful
+    fn decode_fields(fields: &mut Fields) -> Result<Receipt, RlpError> {
+        let outcome: &rlp::Item = fields.next_item()?;
+        let (status, post_state): (bool, Option<U256>) = if outcome.bytes()?.len() == 32 {
+            (true, Some(outcome.hash()?))
+        } else {
+            (outcome.decode()?, None)
+        };
+        let cumulative_gas_used: U256 = fields.next_value()?;
+        let logs_bloom: Bloom = Bloom(fields.next_item()?.fixed_bytes(256)?.try_into().unwrap());
+        let logs: Vec<Log> = fields.next_value()?;
+        return Ok(Receipt {
+            tx_type: TxType::Legacy,
+            status: status,
+            post_state: post_state,
+            cumulative_gas_used: cumulative_gas_used,
+            logs_bloom: logs_bloom,
+            logs: logs
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> Log {
+        return Log::new(U256::new(0x4242), vec![U256::new(1), U256::new(2)], vec![0xAB; 3]);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let receipt: Receipt = Receipt::new(TxType::Legacy, true, U256::new(21000), vec![log()]);
+        let expected: Vec<u8> = rlp::encode_list(&[
+            vec![0x01],
+            rlp::encode_u64(21000),
+            rlp::encode_bytes(&Bloom::from_logs(&[log()]).0),
+            rlp::encode(&vec![log()]),
+        ]);
+        assert_eq!(receipt.encode(), expected);
+
+        // typed receipts carry the type in front, failures a zero status
+        let failed: Receipt = Receipt::new(TxType::DynamicFee, false, U256::new(50000), Vec::new());
+        let encoded: Vec<u8> = failed.encode();
+        assert_eq!(encoded[0], TxType::DynamicFee as u8);
+        assert_eq!(rlp::decode(&encoded[1..]).unwrap().list().unwrap()[0], rlp::Item::Bytes(Vec::new()));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for tx_type in [TxType::Legacy, TxType::AccessList, TxType::Blob] {
+            for status in [true, false] {
+                let receipt: Receipt = Receipt::new(tx_type, status, U256::new(123456), vec![log(), log()]);
+                let decoded: Receipt = Receipt::decode(&receipt.encode()).unwrap();
+                assert_eq!(decoded.tx_type, tx_type);
+                assert_eq!(decoded.status, status);
+                assert_eq!(decoded.post_state, None);
+                assert_eq!(decoded.cumulative_gas_used, U256::new(123456));
+                assert_eq!(decoded.logs_bloom, receipt.logs_bloom);
+                assert_eq!(decoded.logs, receipt.logs);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pre_byzantium() {
+        let mut receipt: Receipt = Receipt::new(TxType::Legacy, true, U256::new(21000), Vec::new());
+        receipt.post_state = Some(U256::MAX - 1);
+        let encoded: Vec<u8> = receipt.encode();
+        assert_eq!(rlp::decode(&encoded).unwrap().list().unwrap()[0].hash(), Ok(U256::MAX - 1));
+
+        let decoded: Receipt = Receipt::decode(&encoded).unwrap();
+        assert_eq!(decoded.post_state, Some(U256::MAX - 1));
+        assert_eq!(decoded.cumulative_gas_used, U256::new(21000));
+    }
+
+    #[test]
+    fn test_bloom() {
+        let receipt: Receipt = Receipt::new(TxType::Legacy, true, U256::new(21000), vec![log()]);
+        let bloom: Bloom = receipt.logs_bloom;
+        assert!(bloom.contains_address(U256::new(0x4242)));
+        assert!(bloom.contains_topic(U256::new(1)));
+        assert!(bloom.contains_topic(U256::new(2)));
+        assert!(!bloom.contains_address(U256::new(0x4243)));
+        assert!(!bloom.contains_topic(U256::new(3)));
+        // three bits at most for each of the address and the two topics
+        let bits: u32 = bloom.0.iter().map(|byte| byte.count_ones()).sum();
+        assert!(bits > 0 && bits <= 9);
+        // the data is not part of the bloom
+        let mut other: Log = log();
+        other.data = Vec::new();
+        assert_eq!(Bloom::from_logs(&[other]), bloom);
+        assert_eq!(Receipt::new(TxType::Legacy, true, U256::new(0), Vec::new()).logs_bloom, Bloom::new());
+    }
+}
@@ -0,0 +1,195 @@
+#![allow(clippy::needless_return)]
+
+use ethnum::{U256};
+use sha2::{Digest, Sha256};
+
+use crate::blockchain::Log;
+
+
+// EIP-7685 execution layer requests: operations the execution layer hands to
+// the consensus layer, committed to in the header through `requests_hash`
+
+pub const DEPOSIT_REQUEST_TYPE: u8 = 0x00;
+pub const WITHDRAWAL_REQUEST_TYPE: u8 = 0x01;
+pub const CONSOLIDATION_REQUEST_TYPE: u8 = 0x02;
+
+// EIP-6110: deposits are read from the mainnet deposit contract's logs
+pub const DEPOSIT_CONTRACT_ADDRESS: U256 = U256::from_words(0x00000000, 0x219ab540356cbb839cbe05303d7705fa);
+// keccak256("DepositEvent(bytes,bytes,bytes,bytes,bytes)")
+pub const DEPOSIT_EVENT_TOPIC: U256 = U256::from_words(
+    0x649bbc62d0e31342afea4e5cd82d4049,
+    0xe7e1ee912fc0889aa790803be39038c5
+);
+
+// EIP-7002 and EIP-7251: queues drained by a system call at the end of every block
+pub const WITHDRAWAL_REQUEST_ADDRESS: U256 = U256::from_words(0x00000961, 0xef480eb55e80d19ad83579a64c007002);
+pub const CONSOLIDATION_REQUEST_ADDRESS: U256 = U256::from_words(0x0000bbdd, 0xc7ce488642fb579f8b00f3a590007251);
+
+const PUBKEY_SIZE: usize = 48;
+const SIGNATURE_SIZE: usize = 96;
+const WITHDRAWAL_REQUEST_SIZE: usize = 20 + PUBKEY_SIZE + 8;
+const CONSOLIDATION_REQUEST_SIZE: usize = 20 + PUBKEY_SIZE + PUBKEY_SIZE;
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositRequest {
+    pub pubkey: Vec<u8>,
+    pub withdrawal_credentials: U256,
+    // in gwei
+    pub amount: u64,
+    pub signature: Vec<u8>,
+    pub index: u64
+}
+
+impl DepositRequest {
+    // the event carries five ABI encoded `bytes`: pubkey, withdrawal credentials,
+    // amount and index as little endian integers, and the signature.
+    // Anything that does not have the deposit contract's layout is ignored
+    pub fn from_log(log: &Log) -> Option<DepositRequest> {
+        if log.address != DEPOSIT_CONTRACT_ADDRESS || log.topics.first() != Some(&DEPOSIT_EVENT_TOPIC) {
+            return None;
+        }
+
+        let sizes: [usize; 5] = [PUBKEY_SIZE, 32, 8, SIGNATURE_SIZE, 8];
+        let mut fields: Vec<&[u8]> = Vec::new();
+        for (i, size) in sizes.iter().enumerate() {
+            let offset: usize = abi_word(&log.data, i * 32)?;
+            let length: usize = abi_word(&log.data, offset)?;
+            if length != *size {
+                return None;
+            }
+            fields.push(log.data.get(offset + 32..offset + 32 + length)?);
+        }
+
+        return Some(DepositRequest {
+            pubkey: fields[0].to_vec(),
+            withdrawal_credentials: U256::from_be_bytes(fields[1].try_into().unwrap()),
+            amount: u64::from_le_bytes(fields[2].try_into().unwrap()),
+            signature: fields[3].to_vec(),
+            index: u64::from_le_bytes(fields[4].try_into().unwrap())
+        });
+    }
+
+    // pubkey ++ withdrawal_credentials ++ amount ++ signature ++ index
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = self.pubkey.clone();
+        data.extend(self.withdrawal_credentials.to_be_bytes());
+        data.extend(self.amount.to_le_bytes());
+        data.extend(&self.signature);
+        data.extend(self.index.to_le_bytes());
+        return data;
+    }
+}
+
+// word at `offset` as a usize, None when it is out of the data or too large
+fn abi_word(data: &[u8], offset: usize) -> Option<usize> {
+    let word: &[u8] = data.get(offset..offset.checked_add(32)?)?;
+    let value: U256 = U256::from_be_bytes(word.try_into().unwrap());
+    if value > U256::from(u32::MAX) {
+        return None;
+    }
+    return Some(value.as_usize());
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithdrawalRequest {
+    pub source_address: U256,
+    pub validator_pubkey: Vec<u8>,
+    // in gwei, zero asks for a full exit
+    pub amount: u64
+}
+
+impl WithdrawalRequest {
+    // source_address ++ validator_pubkey ++ amount
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = self.source_address.to_be_bytes()[12..32].to_vec();
+        data.extend(&self.validator_pubkey);
+        data.extend(self.amount.to_be_bytes());
+        return data;
+    }
+
+    // the system contract returns the requests back to back
+    pub fn decode_all(data: &[u8]) -> Vec<WithdrawalRequest> {
+        return data.chunks_exact(WITHDRAWAL_REQUEST_SIZE).map(|chunk| WithdrawalRequest {
+            source_address: address_from_slice(&chunk[0..20]),
+            validator_pubkey: chunk[20..20 + PUBKEY_SIZE].to_vec(),
+            amount: u64::from_be_bytes(chunk[20 + PUBKEY_SIZE..].try_into().unwrap())
+        }).collect();
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsolidationRequest {
+    pub source_address: U256,
+    pub source_pubkey: Vec<u8>,
+    pub target_pubkey: Vec<u8>
+}
+
+impl ConsolidationRequest {
+    // source_address ++ source_pubkey ++ target_pubkey
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = self.source_address.to_be_bytes()[12..32].to_vec();
+        data.extend(&self.source_pubkey);
+        data.extend(&self.target_pubkey);
+        return data;
+    }
+
+    pub fn decode_all(data: &[u8]) -> Vec<ConsolidationRequest> {
+        return data.chunks_exact(CONSOLIDATION_REQUEST_SIZE).map(|chunk| ConsolidationRequest {
+            source_address: address_from_slice(&chunk[0..20]),
+            source_pubkey: chunk[20..20 + PUBKEY_SIZE].to_vec(),
+            target_pubkey: chunk[20 + PUBKEY_SIZE..].to_vec()
+        }).collect();
+    }
+}
+
+fn address_from_slice(bytes: &[u8]) -> U256 {
+    let mut word: [u8; 32] = [0; 32];
+    word[12..32].copy_from_slice(bytes);
+    return U256::from_be_bytes(word);
+}
+
+
+// requests produced by a block, per type and in the order they were made
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Requests {
+    pub deposits: Vec<DepositRequest>,
+    pub withdrawals: Vec<WithdrawalRequest>,
+    pub consolidations: Vec<ConsolidationRequest>
+}
+
+impl Requests {
+    pub fn new() -> Requests {
+        return Requests::default();
+    }
+
+    // request_type ++ request_data for every type with requests, in type order
+    pub fn encode(&self) -> Vec<Vec<u8>> {
+        let lists: [(u8, Vec<u8>); 3] = [
+            (DEPOSIT_REQUEST_TYPE, self.deposits.iter().flat_map(|request| request.encode()).collect()),
+            (WITHDRAWAL_REQUEST_TYPE, self.withdrawals.iter().flat_map(|request| request.encode()).collect()),
+            (CONSOLIDATION_REQUEST_TYPE, self.consolidations.iter().flat_map(|request| request.encode()).collect()),
+        ];
+
+        let mut encoded: Vec<Vec<u8>> = Vec::new();
+        for (request_type, data) in lists {
+            if !data.is_empty() {
+                let mut request: Vec<u8> = vec![request_type];
+                request.extend(data);
+                encoded.push(request);
+            }
+        }
+        return encoded;
+    }
+
+    // sha256 over the sha256 of every encoded request list
+    pub fn hash(&self) -> U256 {
+        let mut outer = Sha256::new();
+        for request in self.encode() {
+            outer.update(Sha256::digest(&request));
+        }
+        return U256::from_be_bytes(outer.finalize().into());
+    }
+}
@@ -0,0 +1,500 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use ethnum::{U256};
+use std::fmt;
+
+use crate::utils;
+
+
+// Recursive Length Prefix serialization (yellow paper, appendix B)
+
+// lists nested deeper than this are rejected instead of recursing on
+// untrusted input. Nothing the protocol encodes comes close
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    Bytes(Vec<u8>),
+    List(Vec<Item>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RlpError {
+    UnexpectedEnd,
+    TrailingBytes,
+    // a single byte below 0x80 wrapped in a string prefix
+    NonCanonicalSingleByte,
+    // long form used for a short payload, or a length with leading zeros
+    NonCanonicalLength,
+    // integer encoded with leading zero bytes
+    LeadingZeros,
+    ExpectedBytes,
+    ExpectedList,
+    Overflow,
+    InvalidLength { expected: usize, got: usize },
+    FieldCount { expected: usize, got: usize },
+    TooDeep
+}
+
+impl fmt::Display for RlpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RlpError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            RlpError::TrailingBytes => write!(f, "trailing bytes after the item"),
+            RlpError::NonCanonicalSingleByte => write!(f, "single byte encoded as a string"),
+            RlpError::NonCanonicalLength => write!(f, "non canonical length prefix"),
+            RlpError::LeadingZeros => write!(f, "integer with leading zeros"),
+            RlpError::ExpectedBytes => write!(f, "expected a string, found a list"),
+            RlpError::ExpectedList => write!(f, "expected a list, found a string"),
+            RlpError::Overflow => write!(f, "integer too large"),
+            RlpError::InvalidLength { expected, got } =>
+                write!(f, "expected {} bytes, got {}", expected, got),
+            RlpError::FieldCount { expected, got } =>
+                write!(f, "expected {} fields, got {}", expected, got),
+            RlpError::TooDeep => write!(f, "lists nested deeper than {} levels", MAX_DEPTH)
+        }
+    }
+}
+
+impl std::error::Error for RlpError {}
+
+
+fn length_prefix(length: usize, offset: u8) -> Vec<u8> {
+    if length < 56 {
+        return vec![offset + length as u8];
+    }
+    let length_bytes: Vec<u8> = utils::u256_to_minimal_bytes(U256::from(length as u64));
+    let mut prefix: Vec<u8> = vec![offset + 55 + length_bytes.len() as u8];
+    prefix.extend(length_bytes);
+    return prefix;
+}
+
+pub fn encode_bytes(data: &[u8]) -> Vec<u8> {
+    if data.len() == 1 && data[0] < 0x80 {
+        return data.to_vec();
+    }
+    let mut encoded: Vec<u8> = length_prefix(data.len(), 0x80);
+    encoded.extend(data);
+    return encoded;
+}
+
+pub fn encode_u256(value: U256) -> Vec<u8> {
+    return encode_bytes(&utils::u256_to_minimal_bytes(value));
+}
+
+pub fn encode_u64(value: u64) -> Vec<u8> {
+    return encode_u256(U256::from(value));
+}
+
+// addresses and hashes are fixed size strings, not integers
+pub fn encode_address(address: U256) -> Vec<u8> {
+    return encode_bytes(&address.to_be_bytes()[12..32]);
+}
+
+pub fn encode_hash(hash: U256) -> Vec<u8> {
+    return encode_bytes(&hash.to_be_bytes());
+}
+
+// wraps already encoded items into a list
+pub fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut encoded: Vec<u8> = length_prefix(payload.len(), 0xC0);
+    encoded.extend(payload);
+    return encoded;
+}
+
+pub fn encode<T: Encodable>(value: &T) -> Vec<u8> {
+    return value.rlp_encode();
+}
+
+
+// decodes the item at the front of `data`, returning it with the bytes it used
+// `depth` is the number of lists around the item
+fn decode_item(data: &[u8], depth: usize) -> Result<(Item, usize), RlpError> {
+    let prefix: u8 = *data.first().ok_or(RlpError::UnexpectedEnd)?;
+    let (is_list, header, length): (bool, usize, usize) = match prefix {
+        0x00..=0x7F => return Ok((Item::Bytes(vec![prefix]), 1)),
+        0x80..=0xB7 => (false, 1, (prefix - 0x80) as usize),
+        0xC0..=0xF7 => (true, 1, (prefix - 0xC0) as usize),
+        _ => {
+            let is_list: bool = prefix >= 0xF8;
+            let length_size: usize = (prefix - if is_list { 0xF7 } else { 0xB7 }) as usize;
+            let length_bytes: &[u8] = data.get(1..1 + length_size).ok_or(RlpError::UnexpectedEnd)?;
+            if length_bytes[0] == 0 {
+                return Err(RlpError::NonCanonicalLength);
+            }
+            if length_size > 8 {
+                return Err(RlpError::Overflow);
+            }
+            let length: u64 = length_bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64);
+            if length < 56 {
+                return Err(RlpError::NonCanonicalLength);
+            }
+            let length: usize = usize::try_from(length).map_err(|_| RlpError::Overflow)?;
+            (is_list, 1 + length_size, length)
+        }
+    };
+
+    let end: usize = header.checked_add(length).ok_or(RlpError::Overflow)?;
+    let payload: &[u8] = data.get(header..end).ok_or(RlpError::UnexpectedEnd)?;
+    if !is_list {
+        if length == 1 && payload[0] < 0x80 {
+            return Err(RlpError::NonCanonicalSingleByte);
+        }
+        return Ok((Item::Bytes(payload.to_vec()), end));
+    }
+
+    if depth >= MAX_DEPTH {
+        return Err(RlpError::TooDeep);
+    }
+    let mut items: Vec<Item> = Vec::new();
+    let mut offset: usize = 0;
+    while offset < payload.len() {
+        let (item, used) = decode_item(&payload[offset..], depth + 1)?;
+        items.push(item);
+        offset += used;
+    }
+    return Ok((Item::List(items), end));
+}
+
+// decodes `data` as exactly one canonically encoded item
+pub fn decode(data: &[u8]) -> Result<Item, RlpError> {
+    let (item, used) = decode_item(data, 0)?;
+    if used != data.len() {
+        return Err(RlpError::TrailingBytes);
+    }
+    return Ok(item);
+}
+
+pub fn decode_as<T: Decodable>(data: &[u8]) -> Result<T, RlpError> {
+    return T::rlp_decode(&decode(data)?);
+}
+
+
+impl Item {
+    pub fn bytes(&self) -> Result<&[u8], RlpError> {
+        match self {
+            Item::Bytes(bytes) => Ok(bytes),
+            Item::List(_) => Err(RlpError::ExpectedBytes)
+        }
+    }
+
+    pub fn list(&self) -> Result<&[Item], RlpError> {
+        match self {
+            Item::Bytes(_) => Err(RlpError::ExpectedList),
+            Item::List(items) => Ok(items)
+        }
+    }
+
+    pub fn fixed_bytes(&self, size: usize) -> Result<&[u8], RlpError> {
+        let bytes: &[u8] = self.bytes()?;
+        if bytes.len() != size {
+            return Err(RlpError::InvalidLength { expected: size, got: bytes.len() });
+        }
+        return Ok(bytes);
+    }
+
+    pub fn u256(&self) -> Result<U256, RlpError> {
+        let bytes: &[u8] = self.bytes()?;
+        if bytes.len() > 32 {
+            return Err(RlpError::Overflow);
+        }
+        if bytes.first() == Some(&0) {
+            return Err(RlpError::LeadingZeros);
+        }
+        let mut word: [u8; 32] = [0; 32];
+        word[32 - bytes.len()..].copy_from_slice(bytes);
+        return Ok(U256::from_be_bytes(word));
+    }
+
+    pub fn u64(&self) -> Result<u64, RlpError> {
+        let value: U256 = self.u256()?;
+        if value > U256::from(u64::MAX) {
+            return Err(RlpError::Overflow);
+        }
+        return Ok(value.as_u64());
+    }
+
+    pub fn address(&self) -> Result<U256, RlpError> {
+        return Ok(bytes_to_u256(self.fixed_bytes(20)?));
+    }
+
+    pub fn hash(&self) -> Result<U256, RlpError> {
+        return Ok(bytes_to_u256(self.fixed_bytes(32)?));
+    }
+
+    pub fn decode<T: Decodable>(&self) -> Result<T, RlpError> {
+        return T::rlp_decode(self);
+    }
+}
+
+fn bytes_to_u256(bytes: &[u8]) -> U256 {
+    let mut word: [u8; 32] = [0; 32];
+    word[32 - bytes.len()..].copy_from_slice(bytes);
+    return U256::from_be_bytes(word);
+}
+
+
+pub trait Encodable {
+    fn rlp_encode(&self) -> Vec<u8>;
+}
+
+pub trait Decodable: Sized {
+    fn rlp_decode(item: &Item) -> Result<Self, RlpError>;
+}
+
+impl Encodable for U256 {
+    fn rlp_encode(&self) -> Vec<u8> {
+        return encode_u256(*self);
+    }
+}
+
+impl Decodable for U256 {
+    fn rlp_decode(item: &Item) -> Result<U256, RlpError> {
+        return item.u256();
+    }
+}
+
+impl Encodable for u64 {
+    fn rlp_encode(&self) -> Vec<u8> {
+        return encode_u64(*self);
+    }
+}
+
+impl Decodable for u64 {
+    fn rlp_decode(item: &Item) -> Result<u64, RlpError> {
+        return item.u64();
+    }
+}
+
+impl Encodable for bool {
+    fn rlp_encode(&self) -> Vec<u8> {
+        return encode_u64(*self as u64);
+    }
+}
+
+impl Decodable for bool {
+    fn rlp_decode(item: &Item) -> Result<bool, RlpError> {
+        match item.u64()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(RlpError::Overflow)
+        }
+    }
+}
+
+impl<T: Encodable> Encodable for Vec<T> {
+    fn rlp_encode(&self) -> Vec<u8> {
+        let items: Vec<Vec<u8>> = self.iter().map(|item| item.rlp_encode()).collect();
+        return encode_list(&items);
+    }
+}
+
+impl<T: Decodable> Decodable for Vec<T> {
+    fn rlp_decode(item: &Item) -> Result<Vec<T>, RlpError> {
+        return item.list()?.iter().map(T::rlp_decode).collect();
+    }
+}
+
+
+// derive-style encoding for structs serialized as the list of their fields:
+// implementors only list the fields in order, framing and field counting is shared
+pub trait RlpStruct: Sized {
+    fn encode_fields(&self) -> Vec<Vec<u8>>;
+    fn decode_fields(fields: &mut Fields) -> Result<Self, RlpError>;
+}
+
+impl<T: RlpStruct> Encodable for T {
+    fn rlp_encode(&self) -> Vec<u8> {
+        return encode_list(&self.encode_fields());
+    }
+}
+
+impl<T: RlpStruct> Decodable for T {
+    fn rlp_decode(item: &Item) -> Result<T, RlpError> {
+        let mut fields: Fields = Fields::new(item.list()?);
+        let value: T = T::decode_fields(&mut fields)?;
+        fields.finish()?;
+        return Ok(value);
+    }
+}
+
+// cursor over the fields of a list, used by decode_fields implementations
+pub struct Fields<'a> {
+    items: &'a [Item],
+    index: usize
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(items: &'a [Item]) -> Fields<'a> {
+        return Fields {
+            items: items,
+            index: 0
+        };
+    }
+
+    pub fn next_item(&mut self) -> Result<&'a Item, RlpError> {
+        let item: &Item = self.items.get(self.index).ok_or(RlpError::FieldCount {
+            expected: self.index + 1,
+            got: self.items.len()
+        })?;
+        self.index += 1;
+        return Ok(item);
+    }
+
+    pub fn next_value<T: Decodable>(&mut self) -> Result<T, RlpError> {
+        return T::rlp_decode(self.next_item()?);
+    }
+
+    pub fn next_bytes(&mut self) -> Result<Vec<u8>, RlpError> {
+        return Ok(self.next_item()?.bytes()?.to_vec());
+    }
+
+    pub fn next_address(&mut self) -> Result<U256, RlpError> {
+        return self.next_item()?.address();
+    }
+
+    pub fn next_hash(&mut self) -> Result<U256, RlpError> {
+        return self.next_item()?.hash();
+    }
+
+    pub fn remaining(&self) -> usize {
+        return self.items.len() - self.index;
+    }
+
+    pub fn finish(&self) -> Result<(), RlpError> {
+        if self.remaining() != 0 {
+            return Err(RlpError::FieldCount { expected: self.index, got: self.items.len() });
+        }
+        return Ok(());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(data: &str) -> Item {
+        return Item::Bytes(data.as_bytes().to_vec());
+    }
+
+    fn check_decode(cases: &[(&str, Result<Item, RlpError>)]) {
+        for (i, (encoded, expected)) in cases.iter().enumerate() {
+            assert_eq!(&decode(&hex::decode(encoded).unwrap()), expected, "case {}: {}", i, encoded);
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode_bytes(b""), vec![0x80]);
+        assert_eq!(encode_bytes(&[0x00]), vec![0x00]);
+        assert_eq!(encode_bytes(&[0x7F]), vec![0x7F]);
+        assert_eq!(encode_bytes(&[0x80]), vec![0x81, 0x80]);
+        assert_eq!(encode_bytes(b"dog"), hex::decode("83646f67").unwrap());
+        assert_eq!(encode_u64(0), vec![0x80]);
+        assert_eq!(encode_u64(15), vec![0x0F]);
+        assert_eq!(encode_u64(1024), vec![0x82, 0x04, 0x00]);
+        assert_eq!(encode_list(&[]), vec![0xC0]);
+        assert_eq!(encode_list(&[encode_bytes(b"cat"), encode_bytes(b"dog")]), hex::decode("c88363617483646f67").unwrap());
+
+        let lorem: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let mut expected: Vec<u8> = vec![0xB8, 0x38];
+        expected.extend(lorem);
+        assert_eq!(encode_bytes(lorem), expected);
+    }
+
+    #[test]
+    fn test_decode() {
+        check_decode(&[
+            ("80", Ok(bytes(""))),
+            ("00", Ok(Item::Bytes(vec![0x00]))),
+            ("8180", Ok(Item::Bytes(vec![0x80]))),
+            ("83646f67", Ok(bytes("dog"))),
+            ("c0", Ok(Item::List(Vec::new()))),
+            ("c88363617483646f67", Ok(Item::List(vec![bytes("cat"), bytes("dog")]))),
+            // the set theoretical representation of three
+            ("c7c0c1c0c3c0c1c0", Ok(Item::List(vec![
+                Item::List(Vec::new()),
+                Item::List(vec![Item::List(Vec::new())]),
+                Item::List(vec![Item::List(Vec::new()), Item::List(vec![Item::List(Vec::new())])])
+            ]))),
+        ]);
+
+        let mut long: Vec<u8> = vec![0xB8, 0x38];
+        long.extend([0x61; 56]);
+        assert_eq!(decode(&long), Ok(Item::Bytes(vec![0x61; 56])));
+    }
+
+    #[test]
+    fn test_decode_non_canonical() {
+        check_decode(&[
+            // single bytes below 0x80 are their own encoding
+            ("8100", Err(RlpError::NonCanonicalSingleByte)),
+            ("817f", Err(RlpError::NonCanonicalSingleByte)),
+            // long form for payloads that fit the short form
+            ("b800", Err(RlpError::NonCanonicalLength)),
+            ("b80161", Err(RlpError::NonCanonicalLength)),
+            ("f80180", Err(RlpError::NonCanonicalLength)),
+            // length with leading zeros
+            ("b90038", Err(RlpError::NonCanonicalLength)),
+            ("f90038", Err(RlpError::NonCanonicalLength)),
+            // nested items are held to the same rules
+            ("c28100", Err(RlpError::NonCanonicalSingleByte)),
+        ]);
+
+        // integers with leading zeros
+        assert_eq!(decode(&hex::decode("820001").unwrap()).unwrap().u256(), Err(RlpError::LeadingZeros));
+        assert_eq!(decode(&hex::decode("820100").unwrap()).unwrap().u64(), Ok(256));
+        assert_eq!(decode(&hex::decode("80").unwrap()).unwrap().u64(), Ok(0));
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        check_decode(&[
+            ("", Err(RlpError::UnexpectedEnd)),
+            ("83646f", Err(RlpError::UnexpectedEnd)),
+            ("b8", Err(RlpError::UnexpectedEnd)),
+            ("c583646f67", Err(RlpError::UnexpectedEnd)),
+            ("83646f6700", Err(RlpError::TrailingBytes)),
+            ("8080", Err(RlpError::TrailingBytes)),
+            ("bfffffffffffffffff", Err(RlpError::Overflow)),
+        ]);
+
+        // 33 bytes do not fit in a word
+        let mut too_large: Vec<u8> = vec![0xA1];
+        too_large.extend([0x01; 33]);
+        assert_eq!(decode(&too_large).unwrap().u256(), Err(RlpError::Overflow));
+        assert_eq!(decode(&hex::decode("c0").unwrap()).unwrap().u256(), Err(RlpError::ExpectedBytes));
+        assert_eq!(decode(&hex::decode("80").unwrap()).unwrap().list(), Err(RlpError::ExpectedList));
+    }
+
+    // `depth` lists inside each other around nothing
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut encoded: Vec<u8> = encode_list(&[]);
+        for _ in 1..depth {
+            encoded = encode_list(&[encoded]);
+        }
+        return encoded;
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        let mut item: Item = decode(&nested(MAX_DEPTH)).unwrap();
+        for _ in 1..MAX_DEPTH {
+            item = item.list().unwrap()[0].clone();
+        }
+        assert_eq!(item, Item::List(Vec::new()));
+        assert_eq!(decode(&nested(MAX_DEPTH + 1)), Err(RlpError::TooDeep));
+
+        // a million levels with three byte lengths is rejected long before the end
+        let levels: usize = 1_000_000;
+        let mut deep: Vec<u8> = Vec::new();
+        for level in 0..levels {
+            let length: usize = 4 * (levels - level - 1) + 1;
+            deep.extend([0xFA, (length >> 16) as u8, (length >> 8) as u8, length as u8]);
+        }
+        deep.push(0xC0);
+        assert_eq!(decode(&deep), Err(RlpError::TooDeep));
+    }
+}
@@ -0,0 +1,104 @@
+#![allow(clippy::needless_return)]
+
+use ethnum::{U256};
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use std::fmt;
+
+use crate::utils::{self, ADDRESS_MASK};
+
+
+// order of the secp256k1 group
+pub const SECP256K1_N: U256 = U256::from_words(
+    0xfffffffffffffffffffffffffffffffe,
+    0xbaaedce6af48a03bbfd25e8cd0364141
+);
+
+// EIP-2: s above n / 2 is the malleable twin of a valid signature
+pub const SECP256K1_HALF_N: U256 = U256::from_words(
+    0x7fffffffffffffffffffffffffffffff,
+    0x5d576e7357a4501ddfe92f46681b20a0
+);
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignatureError {
+    InvalidV(U256),
+    InvalidR,
+    InvalidS,
+    HighS,
+    InvalidSecretKey,
+    InvalidChainId(U256),
+    RecoveryFailed
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::InvalidV(v) => write!(f, "invalid signature v {}", v),
+            SignatureError::InvalidR => write!(f, "signature r out of range"),
+            SignatureError::InvalidS => write!(f, "signature s out of range"),
+            SignatureError::HighS => write!(f, "signature s above half the curve order"),
+            SignatureError::InvalidSecretKey => write!(f, "invalid secret key"),
+            SignatureError::InvalidChainId(chain_id) => write!(f, "chain id {} does not fit in an EIP-155 v", chain_id),
+            SignatureError::RecoveryFailed => write!(f, "public key recovery failed")
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+
+// the address is the last 20 bytes of keccak256 over the uncompressed public key, without its 0x04 tag
+fn public_key_to_address(key: &VerifyingKey) -> U256 {
+    let point = key.to_encoded_point(false);
+    return utils::keccak256(&point.as_bytes()[1..]) & ADDRESS_MASK;
+}
+
+pub fn secret_key_to_address(secret_key: U256) -> Result<U256, SignatureError> {
+    let key: SigningKey = SigningKey::from_slice(&secret_key.to_be_bytes())
+        .map_err(|_| SignatureError::InvalidSecretKey)?;
+    return Ok(public_key_to_address(key.verifying_key()));
+}
+
+// signs a 32 byte digest, returning (y_parity, r, s) with s already in the lower half
+pub fn sign(hash: U256, secret_key: U256) -> Result<(u8, U256, U256), SignatureError> {
+    let key: SigningKey = SigningKey::from_slice(&secret_key.to_be_bytes())
+        .map_err(|_| SignatureError::InvalidSecretKey)?;
+    let (mut signature, mut recovery_id): (Signature, RecoveryId) = key
+        .sign_prehash_recoverable(&hash.to_be_bytes())
+        .map_err(|_| SignatureError::InvalidSecretKey)?;
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+        recovery_id = RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced());
+    }
+
+    let (r, s) = signature.split_bytes();
+    return Ok((
+        recovery_id.is_y_odd() as u8,
+        U256::from_be_bytes(r.into()),
+        U256::from_be_bytes(s.into())
+    ));
+}
+
+// address that signed `hash`. `y_parity` must already be normalised to 0 or 1
+pub fn recover(hash: U256, y_parity: U256, r: U256, s: U256) -> Result<U256, SignatureError> {
+    if y_parity > 1 {
+        return Err(SignatureError::InvalidV(y_parity));
+    }
+    if r == 0 || r >= SECP256K1_N {
+        return Err(SignatureError::InvalidR);
+    }
+    if s == 0 || s >= SECP256K1_N {
+        return Err(SignatureError::InvalidS);
+    }
+    if s > SECP256K1_HALF_N {
+        return Err(SignatureError::HighS);
+    }
+
+    let signature: Signature = Signature::from_scalars(r.to_be_bytes(), s.to_be_bytes())
+        .map_err(|_| SignatureError::RecoveryFailed)?;
+    let recovery_id: RecoveryId = RecoveryId::new(y_parity == 1, false);
+    let key: VerifyingKey = VerifyingKey::recover_from_prehash(&hash.to_be_bytes(), &signature, recovery_id)
+        .map_err(|_| SignatureError::RecoveryFailed)?;
+    return Ok(public_key_to_address(&key));
+}
@@ -0,0 +1,828 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use ethnum::{U256};
+use std::fmt;
+
+use crate::evm::MAX_INITCODE_SIZE;
+use crate::hardfork::Hardfork;
+use crate::rlp::{self, Fields, RlpError, RlpStruct};
+use crate::signature::{self, SignatureError};
+use crate::utils;
+
+
+const TX_GAS: u64 = 21000;
+const TX_CREATE_GAS: u64 = 32000;
+const TX_DATA_ZERO_GAS: u64 = 4;
+const TX_DATA_NON_ZERO_GAS_FRONTIER: u64 = 68;
+const TX_DATA_NON_ZERO_GAS_ISTANBUL: u64 = 16; // EIP-2028
+const INITCODE_WORD_GAS: u64 = 2; // EIP-3860
+const ACCESS_LIST_ADDRESS_GAS: u64 = 2400; // EIP-2930
+const ACCESS_LIST_STORAGE_KEY_GAS: u64 = 1900;
+
+// EIP-7702: every authorization is paid for as if it created its authority,
+// the difference is refunded when the authority already exists
+pub const PER_EMPTY_ACCOUNT_COST: u64 = 25000;
+pub const PER_AUTH_BASE_COST: u64 = 12500;
+
+// prefix of the message signed by an authorization
+const SET_CODE_MAGIC: u8 = 0x05;
+
+// first byte of a blob versioned hash, the hash of a KZG commitment (EIP-4844)
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+// blob gas consumed by every blob of a type 3 transaction
+pub const GAS_PER_BLOB: u64 = 131072;
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TxType {
+    Legacy = 0,
+    AccessList = 1, // EIP-2930
+    DynamicFee = 2, // EIP-1559
+    Blob = 3, // EIP-4844
+    SetCode = 4 // EIP-7702
+}
+
+impl TxType {
+    pub fn from_u8(value: u8) -> Option<TxType> {
+        match value {
+            0 => Some(TxType::Legacy),
+            1 => Some(TxType::AccessList),
+            2 => Some(TxType::DynamicFee),
+            3 => Some(TxType::Blob),
+            4 => Some(TxType::SetCode),
+            _ => None
+        }
+    }
+
+    // fork that introduced the type
+    pub fn hardfork(&self) -> Hardfork {
+        match self {
+            TxType::Legacy => Hardfork::Frontier,
+            TxType::AccessList => Hardfork::Berlin,
+            TxType::DynamicFee => Hardfork::London,
+            TxType::Blob => Hardfork::Cancun,
+            TxType::SetCode => Hardfork::Prague
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessListItem {
+    pub address: U256,
+    pub storage_keys: Vec<U256>
+}
+
+// EIP-7702 authorization tuple, signed by the EOA delegating its code
+#[derive(Clone, Debug, PartialEq)]
+pub struct Authorization {
+    pub chain_id: U256,
+    pub address: U256,
+    pub nonce: U256,
+    pub y_parity: U256,
+    pub r: U256,
+    pub s: U256
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Transaction {
+    pub tx_type: TxType,
+    // None on legacy transactions signed without EIP-155 replay protection
+    pub chain_id: Option<U256>,
+    pub from: U256,
+    // None creates a contract out of `data`
+    pub to: Option<U256>,
+    pub nonce: U256,
+    pub gas_limit: U256,
+    // legacy and access list transactions carry a single gas price, stored in both fee fields
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub access_list: Vec<AccessListItem>,
+    pub max_fee_per_blob_gas: U256,
+    pub blob_versioned_hashes: Vec<U256>,
+    pub authorization_list: Vec<Authorization>,
+    // `v` as found on the wire: 27/28 or EIP-155 for legacy, the y parity for typed transactions
+    pub v: U256,
+    pub r: U256,
+    pub s: U256
+}
+
+impl Transaction {
+    // a transaction paying a flat gas price, the whole price above the base fee is tip
+    pub fn new(from: U256, to: Option<U256>, nonce: U256, gas_limit: U256, gas_price: U256, value: U256, data: Vec<u8>) -> Transaction {
+        return Transaction {
+            tx_type: TxType::Legacy,
+            chain_id: None,
+            from: from,
+            to: to,
+            nonce: nonce,
+            gas_limit: gas_limit,
+            max_fee_per_gas: gas_price,
+            max_priority_fee_per_gas: gas_price,
+            value: value,
+            data: data,
+            access_list: Vec::new(),
+            max_fee_per_blob_gas: U256::new(0),
+            blob_versioned_hashes: Vec::new(),
+            authorization_list: Vec::new(),
+            v: U256::new(0),
+            r: U256::new(0),
+            s: U256::new(0)
+        };
+    }
+
+    pub fn is_create(&self) -> bool {
+        return self.to.is_none();
+    }
+
+    // price per gas actually paid once the block's base fee is known
+    pub fn effective_gas_price(&self, basefee: U256) -> U256 {
+        let tip: U256 = self.max_fee_per_gas.saturating_sub(basefee).min(self.max_priority_fee_per_gas);
+        return basefee + tip;
+    }
+
+    pub fn blob_gas(&self) -> U256 {
+        return U256::from(GAS_PER_BLOB) * U256::from(self.blob_versioned_hashes.len() as u64);
+    }
+
+    // the most the sender can be charged: gas and blob gas at their fee caps
+    // plus the value. None when it does not fit in 256 bits
+    pub fn max_cost(&self) -> Option<U256> {
+        let gas_cost: U256 = self.gas_limit.checked_mul(self.max_fee_per_gas)?;
+        let blob_cost: U256 = self.blob_gas().checked_mul(self.max_fee_per_blob_gas)?;
+        return gas_cost.checked_add(blob_cost)?.checked_add(self.value);
+    }
+
+    // gas charged before the first instruction runs
+    pub fn intrinsic_gas(&self, hardfork: Hardfork) -> U256 {
+        let mut gas: u64 = TX_GAS;
+        if self.is_create() && hardfork.is_enabled(Hardfork::Homestead) {
+            gas += TX_CREATE_GAS;
+        }
+
+        let non_zero_gas: u64 = if hardfork.is_enabled(Hardfork::Istanbul) {
+            TX_DATA_NON_ZERO_GAS_ISTANBUL
+        } else {
+            TX_DATA_NON_ZERO_GAS_FRONTIER
+        };
+        for byte in &self.data {
+            gas += if *byte == 0 { TX_DATA_ZERO_GAS } else { non_zero_gas };
+        }
+
+        if self.is_create() && hardfork.is_enabled(Hardfork::Shanghai) {
+            gas += INITCODE_WORD_GAS * self.data.len().div_ceil(32) as u64;
+        }
+
+        for item in &self.access_list {
+            gas += ACCESS_LIST_ADDRESS_GAS + ACCESS_LIST_STORAGE_KEY_GAS * item.storage_keys.len() as u64;
+        }
+        gas += PER_EMPTY_ACCOUNT_COST * self.authorization_list.len() as u64;
+        return U256::from(gas);
+    }
+
+    // stateless checks of the type specific fields against the chain configuration
+    pub fn validate(&self, hardfork: Hardfork, chain_id: U256) -> Result<(), TransactionError> {
+        if !hardfork.is_enabled(self.tx_type.hardfork()) {
+            return Err(TransactionError::TypeNotSupported(self.tx_type as u8));
+        }
+        if self.tx_type == TxType::Legacy && self.chain_id.is_some() && !hardfork.is_enabled(Hardfork::SpuriousDragon) {
+            // EIP-155 replay protection, v = chain_id * 2 + 35 is meaningless before it
+            return Err(TransactionError::InvalidSignature(SignatureError::InvalidV(self.v)));
+        }
+        if let Some(id) = self.chain_id {
+            if id != chain_id {
+                return Err(TransactionError::ChainIdMismatch { expected: chain_id, got: id });
+            }
+        }
+        if self.max_priority_fee_per_gas > self.max_fee_per_gas {
+            return Err(TransactionError::PriorityFeeTooHigh);
+        }
+
+        match self.tx_type {
+            TxType::Blob => {
+                if self.is_create() {
+                    return Err(TransactionError::CreateNotAllowed);
+                }
+                if self.blob_versioned_hashes.is_empty() {
+                    return Err(TransactionError::NoBlobs);
+                }
+                let max: u64 = hardfork.max_blobs_per_block();
+                if self.blob_versioned_hashes.len() as u64 > max {
+                    return Err(TransactionError::TooManyBlobs { max: max, got: self.blob_versioned_hashes.len() as u64 });
+                }
+                for hash in &self.blob_versioned_hashes {
+                    if hash.to_be_bytes()[0] != VERSIONED_HASH_VERSION_KZG {
+                        return Err(TransactionError::InvalidBlobVersionedHash(*hash));
+                    }
+                }
+            },
+            TxType::SetCode => {
+                if self.is_create() {
+                    return Err(TransactionError::CreateNotAllowed);
+                }
+                if self.authorization_list.is_empty() {
+                    return Err(TransactionError::EmptyAuthorizationList);
+                }
+            },
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    pub fn hash(&self) -> U256 {
+        return utils::keccak256(&self.encode());
+    }
+
+    // digest covered by the signature. EIP-155 legacy transactions append
+    // [chain_id, 0, 0] in place of the signature fields
+    pub fn signing_hash(&self) -> U256 {
+        let mut fields: Vec<Vec<u8>> = self.encode_fields(false);
+        if self.tx_type == TxType::Legacy {
+            if let Some(chain_id) = self.chain_id {
+                fields.push(rlp::encode_u256(chain_id));
+                fields.push(rlp::encode_u256(U256::new(0)));
+                fields.push(rlp::encode_u256(U256::new(0)));
+            }
+            return utils::keccak256(&rlp::encode_list(&fields));
+        }
+
+        let mut payload: Vec<u8> = vec![self.tx_type as u8];
+        payload.extend(rlp::encode_list(&fields));
+        return utils::keccak256(&payload);
+    }
+
+    // recovery id hidden in `v`: 27/28 for unprotected legacy transactions,
+    // chain_id * 2 + 35/36 with EIP-155 and a plain 0/1 for typed transactions
+    pub fn y_parity(&self) -> Result<U256, SignatureError> {
+        if self.tx_type != TxType::Legacy {
+            if self.v > 1 {
+                return Err(SignatureError::InvalidV(self.v));
+            }
+            return Ok(self.v);
+        }
+
+        let base: U256 = match self.chain_id {
+            Some(chain_id) => eip155_v(chain_id).ok_or(SignatureError::InvalidV(self.v))?,
+            None => U256::new(27)
+        };
+        if self.v < base || self.v - base > 1 {
+            return Err(SignatureError::InvalidV(self.v));
+        }
+        return Ok(self.v - base);
+    }
+
+    // fills in v, r, s and the sender. Legacy transactions are replay protected
+    // whenever a chain id is set
+    pub fn sign(&mut self, secret_key: U256) -> Result<(), SignatureError> {
+        let (y_parity, r, s): (u8, U256, U256) = signature::sign(self.signing_hash(), secret_key)?;
+        self.v = match (self.tx_type, self.chain_id) {
+            (TxType::Legacy, Some(chain_id)) => eip155_v(chain_id)
+                .and_then(|base| base.checked_add(U256::from(y_parity)))
+                .ok_or(SignatureError::InvalidChainId(chain_id))?,
+            (TxType::Legacy, None) => U256::from(27 + y_parity),
+            _ => U256::from(y_parity)
+        };
+        self.r = r;
+        self.s = s;
+        self.from = signature::secret_key_to_address(secret_key)?;
+        return Ok(());
+    }
+
+    pub fn recover_sender(&self) -> Result<U256, SignatureError> {
+        return signature::recover(self.signing_hash(), self.y_parity()?, self.r, self.s);
+    }
+
+    // consensus encoding: rlp(fields) for legacy, type || rlp(fields) otherwise
+    pub fn encode(&self) -> Vec<u8> {
+        let fields: Vec<Vec<u8>> = self.encode_fields(true);
+        let payload: Vec<u8> = rlp::encode_list(&fields);
+        if self.tx_type == TxType::Legacy {
+            return payload;
+        }
+
+        let mut encoded: Vec<u8> = vec![self.tx_type as u8];
+        encoded.extend(payload);
+        return encoded;
+    }
+
+    fn encode_fields(&self, with_signature: bool) -> Vec<Vec<u8>> {
+        let to: Vec<u8> = match self.to {
+            Some(to) => rlp::encode_address(to),
+            None => rlp::encode_bytes(&[])
+        };
+
+        let mut fields: Vec<Vec<u8>> = Vec::new();
+        if self.tx_type != TxType::Legacy {
+            fields.push(rlp::encode_u256(self.chain_id.unwrap_or_default()));
+        }
+        fields.push(rlp::encode_u256(self.nonce));
+        match self.tx_type {
+            TxType::Legacy | TxType::AccessList => {
+                fields.push(rlp::encode_u256(self.max_fee_per_gas));
+            },
+            _ => {
+                fields.push(rlp::encode_u256(self.max_priority_fee_per_gas));
+                fields.push(rlp::encode_u256(self.max_fee_per_gas));
+            }
+        }
+        fields.push(rlp::encode_u256(self.gas_limit));
+        fields.push(to);
+        fields.push(rlp::encode_u256(self.value));
+        fields.push(rlp::encode_bytes(&self.data));
+
+        if self.tx_type != TxType::Legacy {
+            fields.push(rlp::encode(&self.access_list));
+        }
+        if self.tx_type == TxType::Blob {
+            let hashes: Vec<Vec<u8>> = self.blob_versioned_hashes.iter().map(|hash| rlp::encode_hash(*hash)).collect();
+            fields.push(rlp::encode_u256(self.max_fee_per_blob_gas));
+            fields.push(rlp::encode_list(&hashes));
+        }
+        if self.tx_type == TxType::SetCode {
+            fields.push(rlp::encode(&self.authorization_list));
+        }
+
+        if with_signature {
+            fields.push(rlp::encode_u256(self.v));
+            fields.push(rlp::encode_u256(self.r));
+            fields.push(rlp::encode_u256(self.s));
+        }
+        return fields;
+    }
+
+    // decodes a raw transaction as broadcast on the network. Blob transactions
+    // may come wrapped with their sidecar, which is dropped. The sender is
+    // recovered from the signature
+    pub fn decode(raw: &[u8]) -> Result<Transaction, TransactionError> {
+        let mut tx: Transaction = Transaction::decode_unsigned(raw)?;
+        tx.from = tx.recover_sender()?;
+        return Ok(tx);
+    }
+
+    fn decode_unsigned(raw: &[u8]) -> Result<Transaction, TransactionError> {
+        let first: u8 = *raw.first().ok_or(TransactionError::Rlp(RlpError::UnexpectedEnd))?;
+        if first >= 0xC0 {
+            let item: rlp::Item = rlp::decode(raw)?;
+            return Ok(Transaction::decode_fields(TxType::Legacy, item.list()?)?);
+        }
+
+        let tx_type: TxType = TxType::from_u8(first).ok_or(TransactionError::TypeNotSupported(first))?;
+        let item: rlp::Item = rlp::decode(&raw[1..])?;
+        let mut items: &[rlp::Item] = item.list()?;
+        if tx_type == TxType::Blob && items.len() == 4 && items[0].list().is_ok() {
+            // [tx_payload_body, blobs, commitments, proofs]
+            items = items[0].list()?;
+        }
+        return Ok(Transaction::decode_fields(tx_type, items)?);
+    }
+
+    fn decode_fields(tx_type: TxType, items: &[rlp::Item]) -> Result<Transaction, RlpError> {
+        let mut fields: Fields = Fields::new(items);
+        let mut tx: Transaction = Transaction::new(U256::new(0), None, U256::new(0), U256::new(0), U256::new(0), U256::new(0), Vec::new());
+        tx.tx_type = tx_type;
+
+        if tx_type != TxType::Legacy {
+            tx.chain_id = Some(fields.next_value()?);
+        }
+        tx.nonce = fields.next_value()?;
+        if tx_type == TxType::Legacy || tx_type == TxType::AccessList {
+            tx.max_fee_per_gas = fields.next_value()?;
+            tx.max_priority_fee_per_gas = tx.max_fee_per_gas;
+        } else {
+            tx.max_priority_fee_per_gas = fields.next_value()?;
+            tx.max_fee_per_gas = fields.next_value()?;
+        }
+        tx.gas_limit = fields.next_value()?;
+        let to: &rlp::Item = fields.next_item()?;
+        tx.to = if to.bytes()?.is_empty() { None } else { Some(to.address()?) };
+        tx.value = fields.next_value()?;
+        tx.data = fields.next_bytes()?;
+
+        if tx_type != TxType::Legacy {
+            tx.access_list = fields.next_value()?;
+        }
+        if tx_type == TxType::Blob {
+            tx.max_fee_per_blob_gas = fields.next_value()?;
+            tx.blob_versioned_hashes = fields.next_item()?.list()?.iter()
+                .map(|hash| hash.hash())
+                .collect::<Result<Vec<U256>, RlpError>>()?;
+        }
+        if tx_type == TxType::SetCode {
+            tx.authorization_list = fields.next_value()?;
+        }
+
+        tx.v = fields.next_value()?;
+        tx.r = fields.next_value()?;
+        tx.s = fields.next_value()?;
+        fields.finish()?;
+
+        // EIP-155: v = chain_id * 2 + 35 + y_parity
+        if tx_type == TxType::Legacy && tx.v >= 35 {
+            tx.chain_id = Some((tx.v - 35) / 2);
+        }
+        return Ok(tx);
+    }
+}
+
+// EIP-155 v for y parity 0, chain_id * 2 + 35. None when it overflows
+fn eip155_v(chain_id: U256) -> Option<U256> {
+    return chain_id.checked_mul(U256::new(2))?.checked_add(U256::new(35));
+}
+
+impl RlpStruct for AccessListItem {
+    fn encode_fields(&self) -> Vec<Vec<u8>> {
+        let keys: Vec<Vec<u8>> = self.storage_keys.iter().map(|key| rlp::encode_hash(*key)).collect();
+        return vec![
+            rlp::encode_address(self.address),
+            rlp::encode_list(&keys),
+        ];
+    }
+
+    fn decode_fields(fields: &mut Fields) -> Result<AccessListItem, RlpError> {
+        let address: U256 = fields.next_address()?;
+        let storage_keys: Vec<U256> = fields.next_item()?.list()?.iter()
+            .map(|key| key.hash())
+            .collect::<Result<Vec<U256>, RlpError>>()?;
+        return Ok(AccessListItem {
+            address: address,
+            storage_keys: storage_keys
+        });
+    }
+}
+
+impl Authorization {
+    // keccak256(0x05 || rlp([chain_id, address, nonce]))
+    pub fn signing_hash(&self) -> U256 {
+        let mut message: Vec<u8> = vec![SET_CODE_MAGIC];
+        message.extend(rlp::encode_list(&[
+            rlp::encode_u256(self.chain_id),
+            rlp::encode_address(self.address),
+            rlp::encode_u256(self.nonce),
+        ]));
+        return utils::keccak256(&message);
+    }
+
+    pub fn sign(&mut self, secret_key: U256) -> Result<(), SignatureError> {
+        let (y_parity, r, s): (u8, U256, U256) = signature::sign(self.signing_hash(), secret_key)?;
+        self.y_parity = U256::from(y_parity);
+        self.r = r;
+        self.s = s;
+        return Ok(());
+    }
+
+    pub fn recover_authority(&self) -> Result<U256, SignatureError> {
+        return signature::recover(self.signing_hash(), self.y_parity, self.r, self.s);
+    }
+}
+
+impl RlpStruct for Authorization {
+    fn encode_fields(&self) -> Vec<Vec<u8>> {
+        return vec![
+            rlp::encode_u256(self.chain_id),
+            rlp::encode_address(self.address),
+            rlp::encode_u256(self.nonce),
+            rlp::encode_u256(self.y_parity),
+            rlp::encode_u256(self.r),
+            rlp::encode_u256(self.s),
+        ];
+    }
+
+    fn decode_fields(fields: &mut Fields) -> Result<Authorization, RlpError> {
+        return Ok(Authorization {
+            chain_id: fields.next_value()?,
+            address: fields.next_address()?,
+            nonce: fields.next_value()?,
+            y_parity: fields.next_value()?,
+            r: fields.next_value()?,
+            s: fields.next_value()?
+        });
+    }
+}
+
+
+// reasons a transaction cannot be included at all, the state is left untouched
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransactionError {
+    Rlp(RlpError),
+    InvalidSignature(SignatureError),
+    TypeNotSupported(u8),
+    BlockGasLimitReached { gas_limit: U256, available: U256 },
+    ChainIdMismatch { expected: U256, got: U256 },
+    NonceMismatch { expected: U256, got: U256 },
+    NonceOverflow,
+    SenderNotEOA,
+    InsufficientFunds { required: U256, balance: U256 },
+    CostOverflow,
+    IntrinsicGasTooLow { required: U256, gas_limit: U256 },
+    InitcodeTooLarge,
+    PriorityFeeTooHigh,
+    FeeCapTooLow { max_fee_per_gas: U256, basefee: U256 },
+    CreateNotAllowed,
+    NoBlobs,
+    TooManyBlobs { max: u64, got: u64 },
+    BlobGasLimitReached { blob_gas: U256, available: U256 },
+    BlobFeeCapTooLow { max_fee_per_blob_gas: U256, blob_basefee: U256 },
+    InvalidBlobVersionedHash(U256),
+    EmptyAuthorizationList
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransactionError::Rlp(error) => write!(f, "malformed transaction encoding: {}", error),
+            TransactionError::InvalidSignature(error) => write!(f, "invalid signature: {}", error),
+            TransactionError::TypeNotSupported(tx_type) => write!(f, "transaction type {} not supported", tx_type),
+            TransactionError::BlockGasLimitReached { gas_limit, available } =>
+                write!(f, "gas limit {} exceeds the {} gas left in the block", gas_limit, available),
+            TransactionError::ChainIdMismatch { expected, got } =>
+                write!(f, "chain id mismatch: expected {}, got {}", expected, got),
+            TransactionError::NonceMismatch { expected, got } =>
+                write!(f, "nonce mismatch: expected {}, got {}", expected, got),
+            TransactionError::NonceOverflow => write!(f, "sender nonce is at its maximum"),
+            TransactionError::SenderNotEOA => write!(f, "sender has deployed code"),
+            TransactionError::InsufficientFunds { required, balance } =>
+                write!(f, "insufficient funds: required {}, balance {}", required, balance),
+            TransactionError::CostOverflow => write!(f, "maximum transaction cost overflows 256 bits"),
+            TransactionError::IntrinsicGasTooLow { required, gas_limit } =>
+                write!(f, "intrinsic gas too low: required {}, gas limit {}", required, gas_limit),
+            TransactionError::InitcodeTooLarge => write!(f, "initcode exceeds {} bytes", MAX_INITCODE_SIZE),
+            TransactionError::PriorityFeeTooHigh => write!(f, "max priority fee exceeds max fee"),
+            TransactionError::FeeCapTooLow { max_fee_per_gas, basefee } =>
+                write!(f, "max fee per gas {} below base fee {}", max_fee_per_gas, basefee),
+            TransactionError::CreateNotAllowed => write!(f, "transaction type cannot create contracts"),
+            TransactionError::NoBlobs => write!(f, "blob transaction without blobs"),
+            TransactionError::TooManyBlobs { max, got } =>
+                write!(f, "{} blobs, at most {} fit in a block", got, max),
+            TransactionError::BlobGasLimitReached { blob_gas, available } =>
+                write!(f, "blob gas {} exceeds the {} blob gas left in the block", blob_gas, available),
+            TransactionError::BlobFeeCapTooLow { max_fee_per_blob_gas, blob_basefee } =>
+                write!(f, "max fee per blob gas {} below blob base fee {}", max_fee_per_blob_gas, blob_basefee),
+            TransactionError::InvalidBlobVersionedHash(hash) =>
+                write!(f, "blob versioned hash {:#x} has an unknown version", hash),
+            TransactionError::EmptyAuthorizationList => write!(f, "set code transaction without authorizations")
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+impl From<RlpError> for TransactionError {
+    fn from(error: RlpError) -> TransactionError {
+        return TransactionError::Rlp(error);
+    }
+}
+
+impl From<SignatureError> for TransactionError {
+    fn from(error: SignatureError) -> TransactionError {
+        return TransactionError::InvalidSignature(error);
+    }
+}
+
+
+// what a successfully included transaction did
+#[derive(Clone, Debug)]
+pub struct ExecutionResult {
+    pub success: bool,
+    pub gas_used: U256,
+    pub output: Vec<u8>,
+    pub contract_address: Option<U256>
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::SECP256K1_N;
+
+    fn hex_u256(value: &str) -> U256 {
+        return U256::from_str_hex(value).unwrap();
+    }
+
+    // the example transaction of EIP-155
+    fn eip155_example() -> Transaction {
+        let mut tx: Transaction = Transaction::new(
+            U256::new(0),
+            Some(hex_u256("0x3535353535353535353535353535353535353535")),
+            U256::new(9),
+            U256::new(21000),
+            U256::new(20_000_000_000),
+            U256::new(1_000_000_000_000_000_000),
+            Vec::new()
+        );
+        tx.chain_id = Some(U256::new(1));
+        return tx;
+    }
+
+    const EIP155_KEY: &str = "0x4646464646464646464646464646464646464646464646464646464646464646";
+    const EIP155_RAW: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+    #[test]
+    fn test_eip155_vector() {
+        let mut tx: Transaction = eip155_example();
+        assert_eq!(tx.signing_hash(), hex_u256("0xdaf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"));
+
+        tx.sign(hex_u256(EIP155_KEY)).unwrap();
+        assert_eq!(tx.v, U256::new(37));
+        assert_eq!(tx.r, hex_u256("0x28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276"));
+        assert_eq!(tx.s, hex_u256("0x67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"));
+        assert_eq!(hex::encode(tx.encode()), EIP155_RAW);
+        assert_eq!(tx.from, hex_u256("0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"));
+
+        let decoded: Transaction = Transaction::decode(&hex::decode(EIP155_RAW).unwrap()).unwrap();
+        assert_eq!(decoded.chain_id, Some(U256::new(1)));
+        assert_eq!(decoded.from, tx.from);
+        assert_eq!(decoded.hash(), tx.hash());
+    }
+
+    #[test]
+    fn test_unprotected_legacy() {
+        let mut tx: Transaction = eip155_example();
+        tx.chain_id = None;
+        tx.sign(hex_u256(EIP155_KEY)).unwrap();
+        assert!(tx.v == 27 || tx.v == 28);
+
+        let decoded: Transaction = Transaction::decode(&tx.encode()).unwrap();
+        assert_eq!(decoded.chain_id, None);
+        assert_eq!(decoded.from, hex_u256("0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"));
+    }
+
+    #[test]
+    fn test_typed_round_trip() {
+        // well known development key
+        let key: U256 = hex_u256("0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80");
+        let sender: U256 = hex_u256("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        assert_eq!(signature::secret_key_to_address(key), Ok(sender));
+
+        for tx_type in [TxType::AccessList, TxType::DynamicFee] {
+            let mut tx: Transaction = eip155_example();
+            tx.tx_type = tx_type;
+            tx.max_priority_fee_per_gas = U256::new(1_000_000_000);
+            tx.access_list = vec![AccessListItem {
+                address: hex_u256("0x3535353535353535353535353535353535353535"),
+                storage_keys: vec![U256::new(1), U256::new(2)]
+            }];
+            tx.sign(key).unwrap();
+            assert!(tx.v <= 1);
+
+            let raw: Vec<u8> = tx.encode();
+            assert_eq!(raw[0], tx_type as u8);
+            let decoded: Transaction = Transaction::decode(&raw).unwrap();
+            assert_eq!(decoded.from, sender);
+            assert_eq!(decoded.tx_type, tx_type);
+            assert_eq!(decoded.access_list, tx.access_list);
+            assert_eq!(decoded.encode(), raw);
+        }
+    }
+
+    #[test]
+    fn test_invalid_signatures() {
+        let mut tx: Transaction = eip155_example();
+        tx.sign(hex_u256(EIP155_KEY)).unwrap();
+
+        // EIP-2: the malleable twin with s above n / 2 is rejected
+        let mut high_s: Transaction = tx.clone();
+        high_s.s = SECP256K1_N - tx.s;
+        high_s.v = if tx.v == 37 { U256::new(38) } else { U256::new(37) };
+        assert_eq!(high_s.recover_sender(), Err(SignatureError::HighS));
+        assert_eq!(
+            Transaction::decode(&high_s.encode()).unwrap_err(),
+            TransactionError::InvalidSignature(SignatureError::HighS)
+        );
+
+        let mut bad_v: Transaction = tx.clone();
+        bad_v.v = U256::new(39);
+        assert_eq!(bad_v.recover_sender(), Err(SignatureError::InvalidV(U256::new(39))));
+
+        let mut zero_r: Transaction = tx.clone();
+        zero_r.r = U256::new(0);
+        assert_eq!(zero_r.recover_sender(), Err(SignatureError::InvalidR));
+
+        let mut large_s: Transaction = tx.clone();
+        large_s.s = SECP256K1_N;
+        assert_eq!(large_s.recover_sender(), Err(SignatureError::InvalidS));
+
+        let mut typed: Transaction = eip155_example();
+        typed.tx_type = TxType::DynamicFee;
+        typed.sign(hex_u256(EIP155_KEY)).unwrap();
+        typed.v = U256::new(27);
+        assert_eq!(typed.recover_sender(), Err(SignatureError::InvalidV(U256::new(27))));
+
+        // a chain id too large for v cannot be signed
+        let mut huge_chain: Transaction = eip155_example();
+        huge_chain.chain_id = Some(U256::MAX);
+        assert_eq!(huge_chain.sign(hex_u256(EIP155_KEY)), Err(SignatureError::InvalidChainId(U256::MAX)));
+    }
+
+    fn versioned_hash(version: u8, byte: u8) -> U256 {
+        let mut hash: [u8; 32] = [byte; 32];
+        hash[0] = version;
+        return U256::from_be_bytes(hash);
+    }
+
+    fn blob_example() -> Transaction {
+        let mut tx: Transaction = eip155_example();
+        tx.tx_type = TxType::Blob;
+        tx.max_priority_fee_per_gas = U256::new(1_000_000_000);
+        tx.max_fee_per_blob_gas = U256::new(3_000_000_000);
+        tx.blob_versioned_hashes = vec![versioned_hash(VERSIONED_HASH_VERSION_KZG, 0xaa), versioned_hash(VERSIONED_HASH_VERSION_KZG, 0xbb)];
+        return tx;
+    }
+
+    fn set_code_example() -> Transaction {
+        let mut tx: Transaction = eip155_example();
+        tx.tx_type = TxType::SetCode;
+        tx.max_priority_fee_per_gas = U256::new(1_000_000_000);
+        for (chain_id, nonce) in [(0, 0), (1, 7)] {
+            let mut authorization: Authorization = Authorization {
+                chain_id: U256::new(chain_id),
+                address: hex_u256("0x4242424242424242424242424242424242424242"),
+                nonce: U256::new(nonce),
+                y_parity: U256::new(0),
+                r: U256::new(0),
+                s: U256::new(0)
+            };
+            authorization.sign(hex_u256(EIP155_KEY)).unwrap();
+            tx.authorization_list.push(authorization);
+        }
+        return tx;
+    }
+
+    // signs, encodes and decodes `tx`, checking the decoded copy matches
+    fn round_trip(mut tx: Transaction) -> Transaction {
+        tx.sign(hex_u256(EIP155_KEY)).unwrap();
+        let raw: Vec<u8> = tx.encode();
+        assert_eq!(raw[0], tx.tx_type as u8);
+        let decoded: Transaction = Transaction::decode(&raw).unwrap();
+        assert_eq!(decoded.from, hex_u256("0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f"));
+        assert_eq!(decoded.tx_type, tx.tx_type);
+        assert_eq!(decoded.to, tx.to);
+        assert_eq!(decoded.max_fee_per_blob_gas, tx.max_fee_per_blob_gas);
+        assert_eq!(decoded.blob_versioned_hashes, tx.blob_versioned_hashes);
+        assert_eq!(decoded.authorization_list, tx.authorization_list);
+        assert_eq!(decoded.encode(), raw);
+        return decoded;
+    }
+
+    #[test]
+    fn test_blob_round_trip() {
+        let tx: Transaction = round_trip(blob_example());
+        assert_eq!(tx.validate(Hardfork::Cancun, U256::new(1)), Ok(()));
+        assert_eq!(tx.blob_gas(), U256::from(2 * GAS_PER_BLOB));
+
+        // the network form wraps the transaction with its blobs, commitments and proofs
+        let raw: Vec<u8> = tx.encode();
+        let empty: Vec<u8> = rlp::encode_list(&[]);
+        let mut wrapped: Vec<u8> = vec![TxType::Blob as u8];
+        wrapped.extend(rlp::encode_list(&[raw[1..].to_vec(), empty.clone(), empty.clone(), empty]));
+        let unwrapped: Transaction = Transaction::decode(&wrapped).unwrap();
+        assert_eq!(unwrapped.hash(), tx.hash());
+        assert_eq!(unwrapped.from, tx.from);
+    }
+
+    #[test]
+    fn test_set_code_round_trip() {
+        let tx: Transaction = round_trip(set_code_example());
+        assert_eq!(tx.validate(Hardfork::Prague, U256::new(1)), Ok(()));
+        assert_eq!(tx.authorization_list[0].recover_authority(), Ok(tx.from));
+        assert_eq!(tx.intrinsic_gas(Hardfork::Prague), U256::from(TX_GAS + 2 * PER_EMPTY_ACCOUNT_COST));
+    }
+
+    #[test]
+    fn test_blob_and_set_code_validation() {
+        // each variant survives the wire and is then rejected by validate()
+        let mut no_blobs: Transaction = blob_example();
+        no_blobs.blob_versioned_hashes.clear();
+        let mut blob_create: Transaction = blob_example();
+        blob_create.to = None;
+        let wrong_version: U256 = versioned_hash(0x02, 0xaa);
+        let mut bad_version: Transaction = blob_example();
+        bad_version.blob_versioned_hashes.push(wrong_version);
+        let mut too_many: Transaction = blob_example();
+        too_many.blob_versioned_hashes = vec![versioned_hash(VERSIONED_HASH_VERSION_KZG, 0xcc); 7];
+        let mut no_authorizations: Transaction = set_code_example();
+        no_authorizations.authorization_list.clear();
+        let mut set_code_create: Transaction = set_code_example();
+        set_code_create.to = None;
+
+        let cases: [(Transaction, Hardfork, TransactionError); 8] = [
+            (no_blobs, Hardfork::Cancun, TransactionError::NoBlobs),
+            (blob_create, Hardfork::Cancun, TransactionError::CreateNotAllowed),
+            (bad_version, Hardfork::Cancun, TransactionError::InvalidBlobVersionedHash(wrong_version)),
+            (too_many.clone(), Hardfork::Cancun, TransactionError::TooManyBlobs { max: 6, got: 7 }),
+            (blob_example(), Hardfork::Shanghai, TransactionError::TypeNotSupported(3)),
+            (no_authorizations, Hardfork::Prague, TransactionError::EmptyAuthorizationList),
+            (set_code_create, Hardfork::Prague, TransactionError::CreateNotAllowed),
+            (set_code_example(), Hardfork::Cancun, TransactionError::TypeNotSupported(4)),
+        ];
+        for (i, (tx, hardfork, error)) in cases.into_iter().enumerate() {
+            let decoded: Transaction = round_trip(tx);
+            assert_eq!(decoded.validate(hardfork, U256::new(1)), Err(error), "case {}", i);
+        }
+        // Prague raises the blob limit to 9
+        assert_eq!(round_trip(too_many).validate(Hardfork::Prague, U256::new(1)), Ok(()));
+    }
+}
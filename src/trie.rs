@@ -0,0 +1,407 @@
+#![allow(clippy::needless_return)]
+
+use ethnum::{U256};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::rlp::{self, RlpError};
+use crate::utils;
+
+
+// Hexary Merkle Patricia Trie (yellow paper, appendix D). The trie keeps its
+// key/value pairs sorted and rebuilds the node structure when a root is asked
+// for, state is small enough that this beats maintaining the nodes in place
+
+enum Node {
+    Empty,
+    Leaf(Vec<u8>, Vec<u8>),
+    Extension(Vec<u8>, Box<Node>),
+    Branch(Vec<Node>, Option<Vec<u8>>)
+}
+
+impl Node {
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Node::Empty => rlp::encode_bytes(&[]),
+            Node::Leaf(path, value) => rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(path, true)),
+                rlp::encode_bytes(value),
+            ]),
+            Node::Extension(path, child) => rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(path, false)),
+                child.reference(),
+            ]),
+            Node::Branch(children, value) => {
+                let mut items: Vec<Vec<u8>> = children.iter().map(|child| child.reference()).collect();
+                items.push(rlp::encode_bytes(value.as_deref().unwrap_or(&[])));
+                rlp::encode_list(&items)
+            }
+        }
+    }
+
+    // how a parent points at the node: nodes shorter than a hash are inlined
+    fn reference(&self) -> Vec<u8> {
+        let encoded: Vec<u8> = self.encode();
+        if encoded.len() < 32 {
+            return encoded;
+        }
+        return rlp::encode_hash(utils::keccak256(&encoded));
+    }
+}
+
+
+// compact encoding of a nibble path, the flag nibble tells leaves from
+// extensions and odd from even lengths
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let mut flag: u8 = if leaf { 2 } else { 0 };
+    let mut encoded: Vec<u8> = Vec::new();
+    let rest: &[u8] = if nibbles.len() % 2 == 1 {
+        flag += 1;
+        encoded.push(flag << 4 | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        encoded.push(flag << 4);
+        nibbles
+    };
+    for pair in rest.chunks(2) {
+        encoded.push(pair[0] << 4 | pair[1]);
+    }
+    return encoded;
+}
+
+// inverse of hex_prefix, returns the path and whether it belongs to a leaf
+fn decode_hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), ProofError> {
+    let first: u8 = *encoded.first().ok_or(ProofError::InvalidNode)?;
+    let flag: u8 = first >> 4;
+    if flag > 3 {
+        return Err(ProofError::InvalidNode);
+    }
+    let mut nibbles: Vec<u8> = Vec::new();
+    if flag & 1 == 1 {
+        nibbles.push(first & 0x0F);
+    } else if first & 0x0F != 0 {
+        return Err(ProofError::InvalidNode);
+    }
+    nibbles.extend(to_nibbles(&encoded[1..]));
+    return Ok((nibbles, flag & 2 == 2));
+}
+
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    let mut nibbles: Vec<u8> = Vec::with_capacity(key.len() * 2);
+    for byte in key {
+        nibbles.push(byte >> 4);
+        nibbles.push(byte & 0x0F);
+    }
+    return nibbles;
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    return a.iter().zip(b).take_while(|(x, y)| x == y).count();
+}
+
+// builds the subtrie holding `pairs`, sorted by key, whose first `depth` nibbles are shared
+fn build(pairs: &[(Vec<u8>, &Vec<u8>)], depth: usize) -> Node {
+    if pairs.is_empty() {
+        return Node::Empty;
+    }
+    if pairs.len() == 1 {
+        let (key, value) = &pairs[0];
+        return Node::Leaf(key[depth..].to_vec(), value.to_vec());
+    }
+
+    // sorted keys share exactly the prefix the first and the last one share
+    let first: &[u8] = &pairs[0].0[depth..];
+    let last: &[u8] = &pairs[pairs.len() - 1].0[depth..];
+    let shared: usize = common_prefix(first, last);
+    if shared > 0 {
+        return Node::Extension(first[..shared].to_vec(), Box::new(build(pairs, depth + shared)));
+    }
+
+    let mut value: Option<Vec<u8>> = None;
+    let mut children: Vec<Node> = Vec::with_capacity(16);
+    let mut start: usize = 0;
+    if pairs[0].0.len() == depth {
+        value = Some(pairs[0].1.to_vec());
+        start = 1;
+    }
+    for nibble in 0..16 {
+        let end: usize = start + pairs[start..].iter().take_while(|(key, _)| key[depth] == nibble).count();
+        children.push(build(&pairs[start..end], depth + 1));
+        start = end;
+    }
+    return Node::Branch(children, value);
+}
+
+
+#[derive(Clone, Debug, Default)]
+pub struct Trie {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>
+}
+
+impl Trie {
+    pub fn new() -> Trie {
+        return Trie {
+            entries: BTreeMap::new()
+        };
+    }
+
+    // an empty value deletes the key, the trie cannot tell the two apart
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+        if value.is_empty() {
+            self.entries.remove(key);
+            return;
+        }
+        self.entries.insert(key.to_vec(), value);
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&Vec<u8>> {
+        return self.entries.get(key);
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        self.entries.remove(key);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    fn root(&self) -> Node {
+        let pairs: Vec<(Vec<u8>, &Vec<u8>)> = self.entries.iter()
+            .map(|(key, value)| (to_nibbles(key), value))
+            .collect();
+        return build(&pairs, 0);
+    }
+
+    // the root is always hashed, even when its encoding is short
+    pub fn root_hash(&self) -> U256 {
+        return utils::keccak256(&self.root().encode());
+    }
+
+    // encoded nodes on the path to `key`, starting at the root. Nodes inlined
+    // in their parent are not repeated. The path also proves absent keys
+    pub fn prove(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        let nibbles: Vec<u8> = to_nibbles(key);
+        let root: Node = self.root();
+        let mut proof: Vec<Vec<u8>> = vec![root.encode()];
+        let mut node: &Node = &root;
+        let mut position: usize = 0;
+        loop {
+            let child: &Node = match node {
+                Node::Extension(path, child) if nibbles[position..].starts_with(path) => {
+                    position += path.len();
+                    child
+                },
+                Node::Branch(children, _) if position < nibbles.len() => {
+                    position += 1;
+                    &children[nibbles[position - 1] as usize]
+                },
+                _ => return proof
+            };
+            let encoded: Vec<u8> = child.encode();
+            if encoded.len() >= 32 {
+                proof.push(encoded);
+            }
+            node = child;
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProofError {
+    Rlp(RlpError),
+    // a hashed node on the path is not part of the proof
+    MissingNode,
+    HashMismatch,
+    InvalidNode,
+    // the proven value differs from the claimed one
+    ValueMismatch
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProofError::Rlp(error) => write!(f, "malformed proof node: {}", error),
+            ProofError::MissingNode => write!(f, "proof is missing a node"),
+            ProofError::HashMismatch => write!(f, "proof node does not match its hash"),
+            ProofError::InvalidNode => write!(f, "invalid trie node"),
+            ProofError::ValueMismatch => write!(f, "proven value does not match")
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+impl From<RlpError> for ProofError {
+    fn from(error: RlpError) -> ProofError {
+        return ProofError::Rlp(error);
+    }
+}
+
+// next hashed node of the proof, checked against the hash its parent holds
+fn proof_node(proof: &[Vec<u8>], index: &mut usize, hash: U256) -> Result<rlp::Item, ProofError> {
+    let encoded: &Vec<u8> = proof.get(*index).ok_or(ProofError::MissingNode)?;
+    if utils::keccak256(encoded) != hash {
+        return Err(ProofError::HashMismatch);
+    }
+    *index += 1;
+    return Ok(rlp::decode(encoded)?);
+}
+
+// walks `proof` from `root` along `key`. Returns the value stored under the
+// key, or None when the proof shows the key is absent
+pub fn verify_proof(root: U256, key: &[u8], proof: &[Vec<u8>]) -> Result<Option<Vec<u8>>, ProofError> {
+    let nibbles: Vec<u8> = to_nibbles(key);
+    let mut index: usize = 0;
+    let mut position: usize = 0;
+    let mut node: rlp::Item = proof_node(proof, &mut index, root)?;
+    loop {
+        let child: &rlp::Item = match &node {
+            // empty trie or empty branch slot
+            rlp::Item::Bytes(bytes) if bytes.is_empty() => return Ok(None),
+            rlp::Item::Bytes(_) => return Err(ProofError::InvalidNode),
+            rlp::Item::List(items) if items.len() == 17 => {
+                if position == nibbles.len() {
+                    let value: &[u8] = items[16].bytes()?;
+                    return Ok(if value.is_empty() { None } else { Some(value.to_vec()) });
+                }
+                position += 1;
+                &items[nibbles[position - 1] as usize]
+            },
+            rlp::Item::List(items) if items.len() == 2 => {
+                let (path, leaf): (Vec<u8>, bool) = decode_hex_prefix(items[0].bytes()?)?;
+                if leaf {
+                    if nibbles[position..] == path[..] {
+                        return Ok(Some(items[1].bytes()?.to_vec()));
+                    }
+                    return Ok(None);
+                }
+                if !nibbles[position..].starts_with(&path) {
+                    return Ok(None);
+                }
+                position += path.len();
+                &items[1]
+            },
+            rlp::Item::List(_) => return Err(ProofError::InvalidNode)
+        };
+
+        // children are either inlined or referenced by their hash
+        let next: rlp::Item = match child {
+            rlp::Item::Bytes(bytes) if bytes.len() == 32 => proof_node(proof, &mut index, child.hash()?)?,
+            _ => child.clone()
+        };
+        node = next;
+    }
+}
+
+
+// root of a trie keyed by rlp(index), as the transactions and receipts roots are
+pub fn ordered_root(items: &[Vec<u8>]) -> U256 {
+    let mut trie: Trie = Trie::new();
+    for (index, item) in items.iter().enumerate() {
+        trie.insert(&rlp::encode_u64(index as u64), item.clone());
+    }
+    return trie.root_hash();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trie(pairs: &[(&str, &str)]) -> Trie {
+        let mut trie: Trie = Trie::new();
+        for (key, value) in pairs {
+            trie.insert(key.as_bytes(), value.as_bytes().to_vec());
+        }
+        return trie;
+    }
+
+    fn hex_u256(value: &str) -> U256 {
+        return U256::from_str_hex(value).unwrap();
+    }
+
+    const DOGS: [(&str, &str); 3] = [("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")];
+
+    #[test]
+    fn test_root_hash() {
+        // vectors from the ethereum/tests trie tests
+        assert_eq!(Trie::new().root_hash(), hex_u256("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"));
+        assert_eq!(trie(&DOGS).root_hash(), hex_u256("0x8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"));
+        assert_eq!(
+            trie(&[("do", "verb"), ("dog", "puppy"), ("doge", "coin"), ("horse", "stallion")]).root_hash(),
+            hex_u256("0x5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84")
+        );
+    }
+
+    #[test]
+    fn test_insertion_order_and_removal() {
+        let mut reversed: Trie = Trie::new();
+        for (key, value) in DOGS.iter().rev() {
+            reversed.insert(key.as_bytes(), value.as_bytes().to_vec());
+        }
+        assert_eq!(reversed.root_hash(), trie(&DOGS).root_hash());
+
+        // removing a key, or setting it to empty, leaves the trie without it
+        let mut removed: Trie = trie(&DOGS);
+        removed.remove(b"dogglesworth");
+        assert_eq!(removed.root_hash(), trie(&DOGS[..2]).root_hash());
+        removed.insert(b"dog", Vec::new());
+        assert_eq!(removed.root_hash(), trie(&DOGS[..1]).root_hash());
+        assert_eq!(removed.len(), 1);
+    }
+
+    #[test]
+    fn test_proofs() {
+        let empty: Trie = Trie::new();
+        assert_eq!(verify_proof(empty.root_hash(), b"dog", &empty.prove(b"dog")), Ok(None));
+
+        let dogs: Trie = trie(&DOGS);
+        let root: U256 = dogs.root_hash();
+        for (key, value) in DOGS {
+            let proof: Vec<Vec<u8>> = dogs.prove(key.as_bytes());
+            assert_eq!(verify_proof(root, key.as_bytes(), &proof), Ok(Some(value.as_bytes().to_vec())), "{}", key);
+        }
+        for key in ["do", "dogs", "cat", "doggleswortH"] {
+            let proof: Vec<Vec<u8>> = dogs.prove(key.as_bytes());
+            assert_eq!(verify_proof(root, key.as_bytes(), &proof), Ok(None), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_invalid_proofs() {
+        let dogs: Trie = trie(&[("do", "verb"), ("dog", "puppy"), ("doge", "coin"), ("horse", "stallion")]);
+        let root: U256 = dogs.root_hash();
+        let proof: Vec<Vec<u8>> = dogs.prove(b"doge");
+        assert!(proof.len() > 1);
+
+        assert_eq!(verify_proof(root, b"doge", &[]), Err(ProofError::MissingNode));
+        assert_eq!(verify_proof(root, b"doge", &proof[..proof.len() - 1]), Err(ProofError::MissingNode));
+        assert_eq!(verify_proof(root + 1, b"doge", &proof), Err(ProofError::HashMismatch));
+
+        let mut tampered: Vec<Vec<u8>> = proof.clone();
+        let last: usize = tampered.len() - 1;
+        let byte: usize = tampered[last].len() - 1;
+        tampered[last][byte] ^= 1;
+        assert_eq!(verify_proof(root, b"doge", &tampered), Err(ProofError::HashMismatch));
+
+        // a proof for one trie does not prove anything about another
+        let other: Trie = trie(&DOGS);
+        assert_eq!(verify_proof(other.root_hash(), b"doge", &proof), Err(ProofError::HashMismatch));
+    }
+
+    #[test]
+    fn test_ordered_root() {
+        assert_eq!(ordered_root(&[]), Trie::new().root_hash());
+        let items: Vec<Vec<u8>> = vec![vec![0xAA], vec![0xBB; 40]];
+        let mut expected: Trie = Trie::new();
+        expected.insert(&[0x80], items[0].clone());
+        expected.insert(&[0x01], items[1].clone());
+        assert_eq!(ordered_root(&items), expected.root_hash());
+    }
+}
@@ -1,4 +1,9 @@
+#![allow(clippy::needless_return)]
+
 use ethnum::{U256};
+use sha3::{Digest, Keccak256};
+
+use crate::rlp;
 
 
 pub fn bool_to_u256(value: bool) -> U256 {
@@ -10,42 +15,86 @@ pub fn bool_to_u256(value: bool) -> U256 {
 }
 
 
-pub fn encode_rlp(address: &[u8], nonce: U256) -> Vec<u8> {
-    let mut data: Vec<u8> = Vec::new();
-    if nonce == 0 {
-        data.extend([0xD6, 0x94]);
-        data.extend(address);
-        data.push(0x80); 
-    }
-    else if nonce <= 0x7f {
-        data.extend([0xD6, 0x94]);
-        data.extend(address);
-        data.push(nonce.as_u8());
-    }
-    else if nonce <= 0xff {
-        data.extend([0xD7, 0x94]);
-        data.extend(address);
-        data.push(0x81);
-        data.push(nonce.as_u8());
-    }
-    else if nonce <= 0xffff {
-        data.extend([0xD8, 0x94]);
-        data.extend(address);
-        data.push(0x82);
-        data.extend(nonce.as_u16().to_be_bytes());
-    }
-    else if nonce <= 0xffffff {
-        data.extend([0xD9, 0x94]);
-        data.extend(address);
-        data.push(0x83);
-        data.extend(nonce.as_u32().to_be_bytes());
-    }
-    else {
-        data.extend([0xDA, 0x94]);
-        data.extend(address);
-        data.push(0x84);
-        data.extend(nonce.as_u32().to_be_bytes());
+// lower 160 bits of a word, the part that makes up an address
+pub const ADDRESS_MASK: U256 = U256::from_words(0xffffffff, u128::MAX);
+
+
+pub fn keccak256(data: &[u8]) -> U256 {
+    let mut keccak256 = Keccak256::new();
+    keccak256.update(data);
+    return U256::from_be_bytes(keccak256.finalize().into());
+}
+
+// big-endian bytes without leading zeros, as RLP and the trie expect integers
+pub fn u256_to_minimal_bytes(value: U256) -> Vec<u8> {
+    let bytes: [u8; 32] = value.to_be_bytes();
+    let start: usize = bytes.iter().position(|&b| b != 0).unwrap_or(32);
+    return bytes[start..].to_vec();
+}
+
+// factor * e ** (numerator / denominator) through its Taylor expansion, in
+// integers (EIP-4844). Saturates instead of overflowing on absurd inputs
+pub fn fake_exponential(factor: U256, numerator: U256, denominator: U256) -> U256 {
+    let mut output: U256 = U256::new(0);
+    let mut accumulator: U256 = factor * denominator;
+    let mut i: U256 = U256::new(1);
+    while accumulator > 0 {
+        output = output.saturating_add(accumulator);
+        accumulator = match accumulator.checked_mul(numerator) {
+            Some(product) => product / (denominator * i),
+            None => return U256::MAX
+        };
+        i += 1;
     }
+    return output / denominator;
+}
+
+// address of a contract created with CREATE: keccak256(rlp([sender, nonce]))[12:]
+pub fn create_address(sender: U256, nonce: U256) -> U256 {
+    let data: Vec<u8> = rlp::encode_list(&[rlp::encode_address(sender), rlp::encode_u256(nonce)]);
+    return keccak256(&data) & ADDRESS_MASK;
+}
 
-    return data;
+// keccak256(0xff + sender_address + salt + keccak256(initialisation_code))[12:]
+pub fn create2_address(sender: U256, salt: U256, init_code: &[u8]) -> U256 {
+    let mut data: Vec<u8> = Vec::new();
+    data.push(0xff);
+    data.extend(&sender.to_be_bytes()[12..32]);
+    data.extend(salt.to_be_bytes());
+    data.extend(keccak256(init_code).to_be_bytes());
+    return keccak256(&data) & ADDRESS_MASK;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_exponential() {
+        // factor, numerator, denominator, result, from the EIP-4844 test cases
+        let cases: [(u64, u64, u64, u64); 16] = [
+            (1, 0, 1, 1),
+            (38493, 0, 1000, 38493),
+            (0, 1234, 2345, 0),
+            (1, 2, 1, 6),
+            (1, 4, 2, 6),
+            (1, 3, 1, 16),
+            (1, 6, 2, 18),
+            (1, 4, 1, 49),
+            (1, 8, 2, 50),
+            (10, 8, 2, 542),
+            (11, 8, 2, 596),
+            (1, 5, 1, 136),
+            (1, 5, 2, 11),
+            (2, 5, 2, 23),
+            (1, 50000000, 2225652, 5709098764),
+            (1, 380928, 3338477, 1),
+        ];
+        for (factor, numerator, denominator, expected) in cases {
+            let result: U256 = fake_exponential(U256::from(factor), U256::from(numerator), U256::from(denominator));
+            assert_eq!(result, U256::from(expected), "fake_exponential({}, {}, {})", factor, numerator, denominator);
+        }
+        // saturates instead of overflowing
+        assert_eq!(fake_exponential(U256::new(1), U256::MAX, U256::new(1)), U256::MAX);
+    }
 }